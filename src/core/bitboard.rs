@@ -143,6 +143,13 @@ impl BitXorAssign<Square> for Bitboard {
     }
 }
 
+impl From<Square> for Bitboard {
+    #[inline]
+    fn from(sq: Square) -> Self {
+        Self(1 << sq.int())
+    }
+}
+
 impl FromIterator<Square> for Bitboard {
     #[inline]
     fn from_iter<T: IntoIterator<Item = Square>>(iter: T) -> Self {
@@ -11,13 +11,25 @@ pub struct Board {
     pub zobrist: Zobrist,
     pub piece_bitboards: Pieces,
     pub side_pieces: SidePieces,
-    pub halfmove_clock: u8,
+    pub halfmove_clock: u16,
     pub fullmove_counter: u16,
     pub checkers: Bitboard,
+    pub castling_rooks: CastlingRooks,
+    /// Whether castling moves and FEN castling rights are written the Chess960 way.
+    pub chess960: bool,
 }
 
+/// What `make_move` can't recompute when taking the move back.
+#[derive(Debug, Clone, Copy)]
 pub struct Unmake {
-    board: Board,
+    mov: Move,
+    piece: Piece,
+    captured: Option<Piece>,
+    can_castle: CanCastle,
+    en_passant_target_square: Option<Square>,
+    halfmove_clock: u16,
+    checkers: Bitboard,
+    zobrist: Zobrist,
 }
 
 impl Board {
@@ -31,6 +43,8 @@ impl Board {
         piece_bitboards: Pieces([Bitboard::EMPTY; 6]),
         side_pieces: SidePieces([Bitboard::EMPTY; 2]),
         checkers: Bitboard::EMPTY,
+        castling_rooks: CastlingRooks::STANDARD,
+        chess960: false,
     };
     pub fn swap_side(&mut self) {
         self.active_side = !self.active_side;
@@ -59,10 +73,29 @@ impl Board {
         self[piece.side()] ^= sq;
         self.zobrist.xor_piece(sq, piece);
     }
+    /// Toggles a piece in the bitboards without touching the zobrist hash.
+    #[inline]
+    fn toggle_piece(&mut self, sq: Square, piece: Piece) {
+        self[piece.kind()] ^= sq;
+        self[piece.side()] ^= sq;
+    }
     pub fn make_move(&mut self, mov: Move) -> Unmake {
-        let unmake = Unmake { board: self.clone() };
-
         let from_piece = self.get_square(mov.from()).unwrap();
+        let captured = match mov.flags() {
+            MoveFlags::EnPassant => None,
+            flags if flags.is_capture() => self.get_square_kind(mov.to()).map(|kind| !self.active_side + kind),
+            _ => None,
+        };
+        let unmake = Unmake {
+            mov,
+            piece: from_piece,
+            captured,
+            can_castle: self.can_castle,
+            en_passant_target_square: self.en_passant_target_square,
+            halfmove_clock: self.halfmove_clock,
+            checkers: self.checkers,
+            zobrist: self.zobrist,
+        };
 
         if let Some(sq) = self.en_passant_target_square {
             self.zobrist.xor_en_passant(sq);
@@ -75,33 +108,39 @@ impl Board {
                 Side::Black => self.can_castle.remove(CanCastle::BOTH_BLACK),
             }
         }
-        for sq in [mov.from(), mov.to()] {
-            match sq {
-                Square::A1 => self.can_castle.remove(CanCastle::WHITE_QUEEN_SIDE),
-                Square::H1 => self.can_castle.remove(CanCastle::WHITE_KING_SIDE),
-                Square::A8 => self.can_castle.remove(CanCastle::BLACK_QUEEN_SIDE),
-                Square::H8 => self.can_castle.remove(CanCastle::BLACK_KING_SIDE),
-                _ => {}
+        if !self.can_castle.is_empty() {
+            for side in [White, Black] {
+                for castle in Castle::ALL {
+                    let rook = self.castling_rooks.square(side, castle);
+                    if rook == mov.from() || rook == mov.to() {
+                        self.can_castle.remove(CanCastle::new(side, castle));
+                    }
+                }
             }
         }
         self.zobrist.xor_can_castle(self.can_castle);
-        if let Some(piece) = self.get_square(mov.to()) {
-            self.remove_piece(mov.to(), piece);
+        if let Some(castle) = mov.flags().castle() {
+            // in Chess960 the king can land on the rook's square, so lift both pieces first
+            let rook = self.active_side + Rook;
+            self.remove_piece(mov.from(), from_piece);
+            self.remove_piece(self.castling_rooks.square(self.active_side, castle), rook);
+            self.insert_piece(mov.to(), from_piece);
+            self.insert_piece(Square::new(self.active_side.back_rank(), castle.rook_file()), rook);
+        } else {
+            if let Some(piece) = unmake.captured {
+                self.remove_piece(mov.to(), piece);
+            }
+            self.remove_piece(mov.from(), from_piece);
+            self.insert_piece(mov.to(), from_piece);
         }
-        self.remove_piece(mov.from(), from_piece);
-        self.insert_piece(mov.to(), from_piece);
 
         match mov.flags() {
-            MoveFlags::Quiet | MoveFlags::Capture => {}
+            MoveFlags::Quiet | MoveFlags::Capture | MoveFlags::KingCastle | MoveFlags::QueenCastle => {}
             MoveFlags::EnPassant => {
                 let back = mov.to().add_rank(-self.active_side.forward()).unwrap();
                 let pawn = !self.active_side + Pawn;
                 self.remove_piece(back, pawn);
             }
-            MoveFlags::QueenCastle if self.active_side == White => self.swap(Square::A1, Square::D1),
-            MoveFlags::QueenCastle => self.swap(Square::A8, Square::D8),
-            MoveFlags::KingCastle if self.active_side == White => self.swap(Square::F1, Square::H1),
-            MoveFlags::KingCastle => self.swap(Square::F8, Square::H8),
             MoveFlags::DoublePawnPush => {
                 let back = mov.to().add_rank(-self.active_side.forward()).unwrap();
                 self.en_passant_target_square = Some(back);
@@ -115,11 +154,40 @@ impl Board {
             _ => unreachable!("{:?}", mov.flags()),
         }
         self.increment_ply();
+        if from_piece.kind() == Pawn || mov.flags().is_capture() {
+            self.halfmove_clock = 0;
+        }
         self.update_checkers();
         unmake
     }
     pub fn unmake_move(&mut self, unmake: Unmake) {
-        *self = unmake.board;
+        let mov = unmake.mov;
+        self.active_side = !self.active_side;
+        if self.active_side == Black {
+            self.fullmove_counter -= 1;
+        }
+        let side = self.active_side;
+        match mov.flags().promotion() {
+            Some(promotion) => self.toggle_piece(mov.to(), side + PieceKind::from(promotion)),
+            None => self.toggle_piece(mov.to(), unmake.piece),
+        }
+        self.toggle_piece(mov.from(), unmake.piece);
+        if let Some(piece) = unmake.captured {
+            self.toggle_piece(mov.to(), piece);
+        }
+
+        if mov.flags() == MoveFlags::EnPassant {
+            let back = mov.to().add_rank(-side.forward()).unwrap();
+            self.toggle_piece(back, !side + Pawn);
+        } else if let Some(castle) = mov.flags().castle() {
+            self.toggle_piece(Square::new(side.back_rank(), castle.rook_file()), side + Rook);
+            self.toggle_piece(self.castling_rooks.square(side, castle), side + Rook);
+        }
+        self.can_castle = unmake.can_castle;
+        self.en_passant_target_square = unmake.en_passant_target_square;
+        self.halfmove_clock = unmake.halfmove_clock;
+        self.checkers = unmake.checkers;
+        self.zobrist = unmake.zobrist;
     }
     pub fn make_null_move(&mut self) -> Option<Square> {
         self.increment_ply();
@@ -147,6 +215,14 @@ impl Board {
             self.fullmove_counter -= 1;
         }
     }
+    /// Whether the fifty-move rule has made the game a draw.
+    ///
+    /// This doesn't check for checkmate, which takes precedence on the last move.
+    #[must_use]
+    #[inline]
+    pub fn fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
     #[must_use]
     #[inline]
     pub fn side_bitboards(&self, side: Side) -> Pieces {
@@ -315,3 +391,33 @@ impl IndexMut<Side> for Board {
         &mut self.side_pieces[side]
     }
 }
+
+#[test]
+fn test_halfmove_clock() {
+    let mut board = Board::start_pos();
+    for (mov, clock) in [("g1f3", 1), ("b8c6", 2), ("e2e4", 0), ("c6d4", 1), ("f3d4", 0)] {
+        let mov = board.find_legal_move(mov.parse().unwrap()).unwrap();
+        let before = board.halfmove_clock;
+        let unmake = board.make_move(mov);
+        assert_eq!(board.halfmove_clock, clock, "{mov}");
+        board.unmake_move(unmake);
+        assert_eq!(board.halfmove_clock, before, "{mov}");
+        board.make_move(mov);
+    }
+    assert!(!board.fifty_move_draw());
+    assert!(Board::from_fen("8/8/8/4k3/8/8/8/4K2Q b - - 100 80").unwrap().fifty_move_draw());
+}
+
+#[test]
+fn test_unmake_move() {
+    for mut board in [Board::kiwipete(), Board::perft_position_4(), Board::perft_position_5()] {
+        for mov in board.gen_legal_moves() {
+            let (fen, zobrist, checkers) = (board.to_fen(), board.zobrist, board.checkers);
+            let unmake = board.make_move(mov);
+            board.unmake_move(unmake);
+            assert_eq!(board.to_fen(), fen, "{mov}");
+            assert_eq!(board.zobrist, zobrist, "{mov}");
+            assert_eq!(board.checkers, checkers, "{mov}");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::prelude::*;
+
 bitflags::bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
     pub struct CanCastle: u8 {
@@ -13,6 +15,49 @@ bitflags::bitflags! {
     }
 }
 
+impl CanCastle {
+    #[must_use]
+    #[inline]
+    pub const fn new(side: Side, castle: Castle) -> Self {
+        match (side, castle) {
+            (Side::White, Castle::KingSide) => Self::WHITE_KING_SIDE,
+            (Side::White, Castle::QueenSide) => Self::WHITE_QUEEN_SIDE,
+            (Side::Black, Castle::KingSide) => Self::BLACK_KING_SIDE,
+            (Side::Black, Castle::QueenSide) => Self::BLACK_QUEEN_SIDE,
+        }
+    }
+}
+
+/// The files the castling rooks start on.
+///
+/// These are always the a and h files in standard chess, but can be any file in Chess960.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastlingRooks([[File; 2]; 2]);
+
+impl Default for CastlingRooks {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl CastlingRooks {
+    pub const STANDARD: Self = Self([[File(7), File(0)]; 2]);
+    #[must_use]
+    #[inline]
+    pub fn file(self, side: Side, castle: Castle) -> File {
+        self.0[side as usize][castle as usize]
+    }
+    #[inline]
+    pub fn set(&mut self, side: Side, castle: Castle, file: File) {
+        self.0[side as usize][castle as usize] = file;
+    }
+    #[must_use]
+    #[inline]
+    pub fn square(self, side: Side, castle: Castle) -> Square {
+        Square::new(side.back_rank(), self.file(side, castle))
+    }
+}
+
 impl fmt::Display for CanCastle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_empty() {
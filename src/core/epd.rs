@@ -0,0 +1,173 @@
+use std::fmt;
+
+use crate::prelude::*;
+
+/// A line of Extended Position Description: the first four FEN fields followed by operations,
+/// e.g. `r1b1k2r/... w kq - bm Qxf7+; id "WAC.001";`.
+#[derive(Debug, Clone)]
+pub struct Epd {
+    pub board: Board,
+    /// Opcodes and their operands in the order they were written.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    /// Parses a line of EPD. The `hmvc` and `fmvn` opcodes set the move counters that FEN would hold.
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim();
+        let mut fields = [""; 4];
+        for field in &mut fields {
+            let (value, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            *field = value;
+            rest = tail.trim_start();
+        }
+        let board = Board::from_fen(&fields.join(" "))?;
+
+        let mut operations = Vec::new();
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ';');
+            if rest.is_empty() {
+                break;
+            }
+            let end = rest.find(|c: char| c.is_whitespace() || c == ';').unwrap_or(rest.len());
+            let (opcode, tail) = rest.split_at(end);
+            rest = tail;
+            let mut operands = Vec::new();
+            loop {
+                rest = rest.trim_start();
+                if let Some(tail) = rest.strip_prefix(';') {
+                    rest = tail;
+                    break;
+                } else if let Some(quoted) = rest.strip_prefix('"') {
+                    let (operand, tail) = quoted.split_once('"')?;
+                    operands.push(operand.to_string());
+                    rest = tail;
+                } else if rest.is_empty() {
+                    break;
+                } else {
+                    let end = rest.find(|c: char| c.is_whitespace() || c == ';').unwrap_or(rest.len());
+                    operands.push(rest[..end].to_string());
+                    rest = &rest[end..];
+                }
+            }
+            operations.push((opcode.to_string(), operands));
+        }
+
+        let mut epd = Self { board, operations };
+        if let Some(halfmove_clock) = epd.parse_operand("hmvc") {
+            epd.board.halfmove_clock = halfmove_clock;
+        }
+        if let Some(fullmove_counter) = epd.parse_operand("fmvn") {
+            epd.board.fullmove_counter = fullmove_counter;
+        }
+        Some(epd)
+    }
+    #[must_use]
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(name, _)| name == opcode).map(|(_, operands)| operands.as_slice())
+    }
+    /// Replaces the operands of an existing opcode, or adds it at the end.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, old)) => *old = operands,
+            None => self.operations.push((opcode.into(), operands)),
+        }
+    }
+    fn parse_operand<T: std::str::FromStr>(&self, opcode: &str) -> Option<T> {
+        self.operation(opcode)?.first()?.parse().ok()
+    }
+    #[must_use]
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")?.first().map(String::as_str)
+    }
+    /// The moves given by `bm`. Returns `None` if any of them isn't a legal move in SAN.
+    #[must_use]
+    pub fn best_moves(&self) -> Option<Moves> {
+        self.san_moves("bm")
+    }
+    /// The moves given by `am`. Returns `None` if any of them isn't a legal move in SAN.
+    #[must_use]
+    pub fn avoid_moves(&self) -> Option<Moves> {
+        self.san_moves("am")
+    }
+    fn san_moves(&self, opcode: &str) -> Option<Moves> {
+        let mut board = self.board.clone();
+        self.operation(opcode).unwrap_or_default().iter().map(|san| board.parse_san(san)).collect()
+    }
+    /// The analysis count depth, `acd`.
+    #[must_use]
+    pub fn depth(&self) -> Option<u8> {
+        self.parse_operand("acd")
+    }
+    /// The centipawn evaluation, `ce`, from the side to move's point of view.
+    #[must_use]
+    pub fn centipawns(&self) -> Option<i32> {
+        self.parse_operand("ce")
+    }
+    /// Whether playing the move solves the position: it has to be one of the best moves if any are
+    /// given, and none of the moves to avoid. Returns `None` if either list isn't legal moves in SAN.
+    #[must_use]
+    pub fn is_solved_by(&self, mov: Move) -> Option<bool> {
+        let best = self.best_moves()?;
+        let avoid = self.avoid_moves()?;
+        Some((best.is_empty() || best.contains(&mov)) && !avoid.contains(&mov))
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fen = self.board.to_fen();
+        let fields: Vec<&str> = fen.split(' ').take(4).collect();
+        write!(f, "{}", fields.join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {opcode}")?;
+            // ids and comments are strings, which are always quoted
+            let is_string = opcode == "id" || matches!(opcode.as_bytes(), [b'c', b'0'..=b'9']);
+            for operand in operands {
+                if is_string || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';')
+                {
+                    write!(f, " \"{operand}\"")?;
+                } else {
+                    write!(f, " {operand}")?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_epd() {
+    let line = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";"#;
+    let epd = Epd::parse(line).unwrap();
+    assert_eq!(epd.id(), Some("WAC.001"));
+    let best = epd.best_moves().unwrap();
+    assert_eq!(best.iter().map(ToString::to_string).collect::<Vec<_>>(), ["g3g6"]);
+    assert_eq!(epd.is_solved_by(best[0]), Some(true));
+    assert_eq!(epd.to_string(), line);
+
+    let mut epd = Epd::parse(
+        "r1b1k2r/pp1n1ppp/2p1p3/q5B1/1b1P4/P1n1PN2/1P1Q1PPP/2R1KB1R b Kkq - am Bxa3 Qxa3; hmvc 3; c0 \"two; words\"",
+    )
+    .unwrap();
+    assert_eq!(epd.board.halfmove_clock, 3);
+    assert_eq!(epd.avoid_moves().unwrap().len(), 2);
+    assert_eq!(epd.best_moves().unwrap().len(), 0);
+    let bishop_takes = epd.board.clone().parse_san("Bxa3").unwrap();
+    assert_eq!(epd.is_solved_by(bishop_takes), Some(false));
+
+    epd.set_operation("acd", vec!["12".into()]);
+    epd.set_operation("ce", vec!["-35".into()]);
+    assert_eq!((epd.depth(), epd.centipawns()), (Some(12), Some(-35)));
+    let written = epd.to_string();
+    assert!(written.ends_with(r#"am Bxa3 Qxa3; hmvc 3; c0 "two; words"; acd 12; ce -35;"#));
+    assert_eq!(Epd::parse(&written).unwrap().to_string(), written);
+
+    assert!(Epd::parse("8/8/8/8/8/8/8/8 w - - bm Qg6;").unwrap().best_moves().is_none());
+    // a position whose answer can't be read can't be solved, or failed, by any move
+    let epd = Epd::parse(&line.replace("Qg6", "Qz9")).unwrap();
+    assert_eq!(epd.is_solved_by(best[0]), None);
+    assert!(Epd::parse("not an epd").is_none());
+}
@@ -31,7 +31,24 @@ impl Board {
         Self::from_fen(PERFT_POSITION_5).expect("Should be valid FEN")
     }
 
+    /// Writes the position as FEN, using X-FEN castling rights for Chess960 positions.
     pub fn to_fen_into(&self, buf: &mut String) {
+        self.write_fen(buf, false);
+    }
+    #[must_use]
+    pub fn to_fen(&self) -> String {
+        let mut builder = String::new();
+        self.to_fen_into(&mut builder);
+        builder
+    }
+    /// Writes the position as Shredder-FEN, which names the file of every castling rook.
+    #[must_use]
+    pub fn to_shredder_fen(&self) -> String {
+        let mut builder = String::new();
+        self.write_fen(&mut builder, true);
+        builder
+    }
+    fn write_fen(&self, buf: &mut String, shredder: bool) {
         let mut prev = None::<Square>;
         for sq in Square::all() {
             if let Some(piece) = self.get_square(sq.flip()) {
@@ -68,7 +85,12 @@ impl Board {
 
         buf.push(' ');
         buf.push(self.active_side.symbol());
-        write!(buf, " {}", self.can_castle).expect("Writing to a string should not fail");
+        buf.push(' ');
+        if shredder || self.chess960 {
+            self.write_castling_files(buf, shredder);
+        } else {
+            write!(buf, "{}", self.can_castle).expect("Writing to a string should not fail");
+        }
 
         buf.push(' ');
         match self.en_passant_target_square {
@@ -79,11 +101,38 @@ impl Board {
         write!(buf, " {} {}", self.halfmove_clock, self.fullmove_counter)
             .expect("Writing to a string should not fail");
     }
-    #[must_use]
-    pub fn to_fen(&self) -> String {
-        let mut builder = String::new();
-        self.to_fen_into(&mut builder);
-        builder
+    /// Shredder-FEN always writes the rook's file. X-FEN only does when another rook
+    /// is further out on the same side of the king, and writes `KQkq` otherwise.
+    fn write_castling_files(&self, buf: &mut String, shredder: bool) {
+        if self.can_castle.is_empty() {
+            buf.push('-');
+        }
+        for side in [White, Black] {
+            for castle in Castle::ALL {
+                if !self.can_castle.contains(CanCastle::new(side, castle)) {
+                    continue;
+                }
+                let file = self.castling_rooks.file(side, castle);
+                let symbol = if shredder || self.outermost_rook(side, castle) != Some(file) {
+                    (b'a' + file.0) as char
+                } else if castle == Castle::KingSide {
+                    'k'
+                } else {
+                    'q'
+                };
+                buf.push(if side == White { symbol.to_ascii_uppercase() } else { symbol });
+            }
+        }
+    }
+    /// The file of the rook furthest from the king on the back rank, on the given side of the king.
+    fn outermost_rook(&self, side: Side, castle: Castle) -> Option<File> {
+        let king = self.get_king_square(side)?;
+        let rooks = self.get(side + Rook);
+        let mut files = (0..8).map(File).filter(|&file| rooks.contains(Square::new(side.back_rank(), file)));
+        match castle {
+            Castle::KingSide => files.rfind(|file| file.0 > king.file().0),
+            Castle::QueenSide => files.find(|file| file.0 < king.file().0),
+        }
     }
     #[must_use]
     pub fn from_fen(fen: &str) -> Option<Board> {
@@ -95,7 +144,7 @@ impl Board {
             "b" => Black,
             _ => return None,
         };
-        let can_castle = parse_can_castle(fields.next()?)?;
+        let (can_castle, castling_rooks) = parse_can_castle(fields.next()?, &board)?;
         let en_passant_target_square = parse_en_passant(fields.next()?)?;
         let halfmove_clock = fields.next().and_then(|fen| fen.parse().ok()).unwrap_or(0);
         let fullmove_counter = fields.next().and_then(|fen| fen.parse().ok()).unwrap_or(1);
@@ -104,6 +153,13 @@ impl Board {
             board.swap_side();
         }
         board.can_castle = can_castle;
+        board.castling_rooks = castling_rooks;
+        board.chess960 = castling_rooks != CastlingRooks::STANDARD
+            || [White, Black].into_iter().any(|side| {
+                let rights = CanCastle::new(side, Castle::KingSide) | CanCastle::new(side, Castle::QueenSide);
+                can_castle.intersects(rights)
+                    && board.get_king_square(side).is_some_and(|king| king.file() != File(4))
+            });
         board.zobrist.xor_can_castle(can_castle);
         board.en_passant_target_square = en_passant_target_square;
         board.halfmove_clock = halfmove_clock;
@@ -148,21 +204,34 @@ fn parse_pieces(fen: &str) -> Option<Board> {
     Some(board)
 }
 
-fn parse_can_castle(fen: &str) -> Option<CanCastle> {
+/// Parses standard, X-FEN and Shredder-FEN castling rights.
+///
+/// `K` and `Q` refer to the outermost rook on that side of the king, and a file letter to the rook on that file.
+fn parse_can_castle(fen: &str, board: &Board) -> Option<(CanCastle, CastlingRooks)> {
     let mut can_castle = CanCastle::empty();
+    let mut castling_rooks = CastlingRooks::STANDARD;
 
-    for byte in fen.as_bytes() {
-        match byte {
-            b'-' => return Some(can_castle),
-            b'K' => can_castle |= CanCastle::WHITE_KING_SIDE,
-            b'Q' => can_castle |= CanCastle::WHITE_QUEEN_SIDE,
-            b'k' => can_castle |= CanCastle::BLACK_KING_SIDE,
-            b'q' => can_castle |= CanCastle::BLACK_QUEEN_SIDE,
+    for byte in fen.bytes() {
+        let side = if byte.is_ascii_uppercase() { White } else { Black };
+        let (castle, file) = match byte.to_ascii_lowercase() {
+            b'-' => return Some((can_castle, castling_rooks)),
+            b'k' => (Castle::KingSide, board.outermost_rook(side, Castle::KingSide)),
+            b'q' => (Castle::QueenSide, board.outermost_rook(side, Castle::QueenSide)),
+            file @ b'a'..=b'h' => {
+                let file = File(file - b'a');
+                let king = board.get_king_square(side)?;
+                let castle = if file > king.file() { Castle::KingSide } else { Castle::QueenSide };
+                (castle, Some(file))
+            }
             _ => return None,
+        };
+        can_castle |= CanCastle::new(side, castle);
+        if let Some(file) = file {
+            castling_rooks.set(side, castle, file);
         }
     }
 
-    Some(can_castle)
+    Some((can_castle, castling_rooks))
 }
 
 #[allow(clippy::option_option)]
@@ -192,5 +261,25 @@ fn test_fen_parsing() {
 
 #[test]
 fn test_can_castle() {
-    assert_eq!(parse_can_castle("Kkq"), Some(CanCastle::WHITE_KING_SIDE | CanCastle::BOTH_BLACK));
+    let board = Board::start_pos();
+    let (can_castle, _) = parse_can_castle("Kkq", &board).unwrap();
+    assert_eq!(can_castle, CanCastle::WHITE_KING_SIDE | CanCastle::BOTH_BLACK);
+    assert_eq!(parse_can_castle("HAha", &board), parse_can_castle("KQkq", &board));
+}
+
+#[test]
+fn test_chess960_fen() {
+    let shredder = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1";
+    let board = Board::from_fen(shredder).unwrap();
+    assert!(board.chess960);
+    assert_eq!(board.castling_rooks.square(White, Castle::QueenSide), Square::E1);
+    assert_eq!(board.castling_rooks.square(Black, Castle::KingSide), Square::G8);
+    assert_eq!(board.to_shredder_fen(), shredder);
+    assert_eq!(board.to_fen(), "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1");
+
+    // X-FEN names the file when there's a rook further out
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/R3KR1R w AF - 0 1").unwrap();
+    assert_eq!(board.castling_rooks.square(White, Castle::KingSide), Square::F1);
+    assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/R3KR1R w FQ - 0 1");
+    assert_eq!(board.to_shredder_fen(), "4k3/8/8/8/8/8/8/R3KR1R w FA - 0 1");
 }
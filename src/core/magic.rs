@@ -138,7 +138,7 @@ impl<const PIECE: usize> SquareTables<PIECE> {
                 result.insert(target_square);
                 if occupancy.contains(target_square) {
                     break;
-                };
+                }
             }
         }
         result.0
@@ -151,7 +151,7 @@ fn index_to_bb(index: usize, mut m: u64) -> Bitboard {
         let j = pop_1st_bit(&mut m);
         if (index & (1 << i)) > 0 {
             result.0 |= 1 << j;
-        };
+        }
     }
     result
 }
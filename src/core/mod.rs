@@ -1,13 +1,17 @@
 pub mod bitboard;
 pub mod board;
 pub mod can_castle;
+pub mod epd;
 pub mod fen;
 pub mod magic;
 pub mod r#move;
 pub mod move_flags;
 pub mod movegen;
 pub mod perft;
+pub mod pgn;
 pub mod piece;
+pub mod san;
+pub mod see;
 pub mod side;
 pub mod square;
 pub mod zobrist;
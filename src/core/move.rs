@@ -3,7 +3,7 @@ use std::str::FromStr;
 
 use crate::prelude::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Move(u16);
 
 impl Move {
@@ -13,6 +13,17 @@ impl Move {
     pub fn new(from: Square, to: Square, flags: MoveFlags) -> Self {
         Self((u16::from(from)) | (u16::from(to)) << 6 | (flags as u16) << 12)
     }
+    /// Rebuilds a move from `int`, which must have come from a valid move.
+    #[must_use]
+    #[inline]
+    pub(crate) fn from_int(int: u16) -> Self {
+        Self(int)
+    }
+    #[must_use]
+    #[inline]
+    pub fn int(self) -> u16 {
+        self.0
+    }
     #[inline]
     #[must_use]
     pub fn from(self) -> Square {
@@ -54,11 +54,42 @@ impl MoveFlags {
     }
     #[must_use]
     #[inline]
+    pub fn castle(self) -> Option<Castle> {
+        match self {
+            Self::KingCastle => Some(Castle::KingSide),
+            Self::QueenCastle => Some(Castle::QueenSide),
+            _ => None,
+        }
+    }
+    #[must_use]
+    #[inline]
     pub fn is_capture(self) -> bool {
         self as u8 & 0b0100 == 0b0100
     }
 }
 
+impl Castle {
+    pub const ALL: [Self; 2] = [Self::KingSide, Self::QueenSide];
+    /// The file the king lands on, which is the same in Chess960.
+    #[must_use]
+    #[inline]
+    pub const fn king_file(self) -> File {
+        match self {
+            Self::KingSide => File(6),
+            Self::QueenSide => File(2),
+        }
+    }
+    /// The file the rook lands on, which is the same in Chess960.
+    #[must_use]
+    #[inline]
+    pub const fn rook_file(self) -> File {
+        match self {
+            Self::KingSide => File(5),
+            Self::QueenSide => File(3),
+        }
+    }
+}
+
 impl From<Castle> for MoveFlags {
     fn from(value: Castle) -> Self {
         match value {
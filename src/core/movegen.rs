@@ -6,21 +6,45 @@ pub const DIRECTION_OFFSETS: [i8; 8] = [8, -8, -1, 1, 7, -7, 9, -9];
 pub const NUM_SQUARES_TO_EDGE: [[i8; 8]; 64] = compute_num_squares_to_edge();
 pub const KING_MOVES: [Bitboard; 64] = compute_king_moves();
 pub const KNIGHT_MOVES: [Bitboard; 64] = compute_knight_moves();
-const ATTACK_PAWN_MOVES: [[Bitboard; 64]; 2] = compute_pawn_moves();
+pub(crate) const ATTACK_PAWN_MOVES: [[Bitboard; 64]; 2] = compute_pawn_moves();
+/// The squares strictly between two squares on the same rank, file or diagonal.
+static BETWEEN: [[Bitboard; 64]; 64] = compute_between();
+/// The whole rank, file or diagonal through two squares, empty if they don't share one.
+static LINE: [[Bitboard; 64]; 64] = compute_lines();
 
 pub struct CapturesOnly;
+pub struct QuietsOnly;
 pub struct FullGen;
 
+/// Which kinds of moves a `MoveGenerator` generates. Promotions count as captures only when they
+/// capture.
 pub trait GenType {
-    const CAPTURES_ONLY: bool;
+    const CAPTURES: bool;
+    const QUIETS: bool;
 }
 
 impl GenType for CapturesOnly {
-    const CAPTURES_ONLY: bool = true;
+    const CAPTURES: bool = true;
+    const QUIETS: bool = false;
+}
+
+impl GenType for QuietsOnly {
+    const CAPTURES: bool = false;
+    const QUIETS: bool = true;
 }
 
 impl GenType for FullGen {
-    const CAPTURES_ONLY: bool = false;
+    const CAPTURES: bool = true;
+    const QUIETS: bool = true;
+}
+
+/// The checks and pins of a position, kept to test several moves with `MoveGenerator::is_legal`
+/// without working them out again for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Legality {
+    check_mask: Bitboard,
+    pinned: Bitboard,
+    king_danger: Bitboard,
 }
 
 pub struct MoveGenerator<'a, G: GenType = FullGen> {
@@ -28,6 +52,12 @@ pub struct MoveGenerator<'a, G: GenType = FullGen> {
     board: &'a mut Board,
     pub queen_knight_promote_only: bool,
     magic: &'static Magic,
+    /// Squares a non-king move must land on: the checker or the squares blocking it.
+    check_mask: Bitboard,
+    /// Friendly pieces that may only move along the line to their king.
+    pinned: Bitboard,
+    /// Squares attacked by the enemy, seeing through the friendly king.
+    king_danger: Bitboard,
     ty: PhantomData<G>,
 }
 
@@ -42,6 +72,126 @@ impl Board {
         movegen.queen_knight_promote_only = false;
         movegen.gen_legal_moves()
     }
+    /// Whether a move, which may come from another position, follows the rules for moving its
+    /// piece here. Whether it leaves the king in check is up to `MoveGenerator::is_legal`.
+    #[must_use]
+    pub fn is_pseudolegal(&self, mov: Move) -> bool {
+        let side = self.active_side;
+        let (from, to, flags) = (mov.from(), mov.to(), mov.flags());
+        let Some(kind) = self.get_square_kind(from).filter(|_| self.is_side(from, side)) else {
+            return false;
+        };
+        if let Some(castle) = flags.castle() {
+            return kind == King
+                && self.can_castle.contains(CanCastle::new(side, castle))
+                && to == Square::new(side.back_rank(), castle.king_file());
+        }
+        match flags {
+            MoveFlags::_6 | MoveFlags::_7 => return false,
+            MoveFlags::EnPassant => {
+                return kind == Pawn
+                    && self.en_passant_target_square == Some(to)
+                    && ATTACK_PAWN_MOVES[side as usize][from].contains(to);
+            }
+            _ => {}
+        }
+        let target_ok = if flags.is_capture() { self.is_side(to, !side) } else { !self.is_piece_at(to) };
+        if !target_ok {
+            return false;
+        }
+        if kind == Pawn {
+            let forward = side.forward();
+            if flags.promotion().is_some() != (to.rank() == (!side).back_rank()) {
+                return false;
+            }
+            return if flags.is_capture() {
+                ATTACK_PAWN_MOVES[side as usize][from].contains(to)
+            } else if flags == MoveFlags::DoublePawnPush {
+                let start_rank = if side == White { 1 } else { 6 };
+                let skipped = from.add_rank(forward);
+                from.rank().0 == start_rank
+                    && skipped.is_some_and(|sq| !self.is_piece_at(sq))
+                    && skipped.and_then(|sq| sq.add_rank(forward)) == Some(to)
+            } else {
+                from.add_rank(forward) == Some(to)
+            };
+        }
+        if flags.promotion().is_some() || flags == MoveFlags::DoublePawnPush {
+            return false;
+        }
+        let magic = Magic::get();
+        let occupancy = self.all_pieces();
+        let attacks = match kind {
+            Knight => KNIGHT_MOVES[from],
+            Bishop => magic.bishop_attacks(from, occupancy),
+            Rook => magic.rook_attacks(from, occupancy),
+            Queen => magic.queen_attacks(from, occupancy),
+            King => KING_MOVES[from],
+            Pawn => unreachable!("Pawn moves are handled above"),
+        };
+        attacks.contains(to)
+    }
+    /// Whether a move puts the opponent in check, found without making it.
+    #[must_use]
+    pub fn gives_check(&self, mov: Move) -> bool {
+        let side = self.active_side;
+        let (from, to) = (mov.from(), mov.to());
+        let Some(enemy_king) = self.get_king_square(!side) else { return false };
+        if mov.flags().castle().is_some() {
+            // the rook's squares depend on the variant, so castling, which is rare, is played out
+            let mut board = self.clone();
+            board.make_move(mov);
+            return board.in_check();
+        }
+        let magic = Magic::get();
+        let mut occupancy = (self.all_pieces() ^ from) | Bitboard::from(to);
+        if mov.flags() == MoveFlags::EnPassant {
+            occupancy ^= to.add_rank(-side.forward()).expect("En passant captures a pawn");
+        }
+        // a slider uncovered by the move, which left `from` and possibly the en passant square
+        let rooks = (self[Rook] | self[Queen]) & self[side] & occupancy;
+        let bishops = (self[Bishop] | self[Queen]) & self[side] & occupancy;
+        let discovered = (magic.rook_attacks(enemy_king, occupancy) & rooks)
+            | (magic.bishop_attacks(enemy_king, occupancy) & bishops);
+        if !discovered.is_empty() {
+            return true;
+        }
+        let kind = match mov.flags().promotion() {
+            Some(promotion) => PieceKind::from(promotion),
+            None => self.get_square_kind(from).expect("The move should have a piece to move"),
+        };
+        let attacks = match kind {
+            Pawn => ATTACK_PAWN_MOVES[side as usize][to],
+            Knight => KNIGHT_MOVES[to],
+            Bishop => magic.bishop_attacks(to, occupancy),
+            Rook => magic.rook_attacks(to, occupancy),
+            Queen => magic.queen_attacks(to, occupancy),
+            King => Bitboard::EMPTY,
+        };
+        attacks.contains(enemy_king)
+    }
+    /// Finds the legal move with the same squares and promotion as `mov`.
+    ///
+    /// Moves parsed from long algebraic notation don't know about captures or castling,
+    /// so their flags can't be compared directly.
+    #[must_use]
+    pub fn find_legal_move(&mut self, mov: Move) -> Option<Move> {
+        self.gen_legal_moves().into_iter().find(|&legal| {
+            let uci = self.uci_move(legal);
+            (uci.from(), uci.to(), uci.flags().promotion()) == (mov.from(), mov.to(), mov.flags().promotion())
+        })
+    }
+    /// The move as it's sent over UCI. In Chess960 the king castles by "capturing" its own rook.
+    #[must_use]
+    pub fn uci_move(&self, mov: Move) -> Move {
+        match mov.flags().castle() {
+            Some(castle) if self.chess960 => {
+                let side = if mov.from().rank() == White.back_rank() { White } else { Black };
+                Move::new(mov.from(), self.castling_rooks.square(side, castle), mov.flags())
+            }
+            _ => mov,
+        }
+    }
     #[must_use]
     pub fn gen_capture_moves(&mut self) -> Moves {
         MoveGenerator::<CapturesOnly>::new(self).gen_legal_moves()
@@ -60,60 +210,130 @@ impl<'a, G: GenType> MoveGenerator<'a, G> {
             board,
             queen_knight_promote_only: true,
             magic: Magic::get(),
+            check_mask: Bitboard::ALL,
+            pinned: Bitboard::EMPTY,
+            king_danger: Bitboard::EMPTY,
             ty: PhantomData,
         }
     }
     #[must_use]
     pub fn gen_legal_moves(&mut self) -> Moves {
-        let mut moves = self.gen_pseudolegal_moves();
-        moves.retain(|&mut mov| self.is_legal(mov));
-        moves
+        self.compute_legality();
+        self.gen_moves()
     }
     #[must_use]
     pub fn gen_pseudolegal_moves(&mut self) -> Moves {
+        self.check_mask = Bitboard::ALL;
+        self.pinned = Bitboard::EMPTY;
+        self.king_danger = Bitboard::EMPTY;
+        self.gen_moves()
+    }
+    fn gen_moves(&mut self) -> Moves {
         let pieces = self.board.friendly_bitboards();
         let all_pieces = self.board.all_pieces();
-        if let Some(king_pos) = self.board.active_king() {
+        if let Some(king_pos) = pieces[King].bitscan() {
             self.gen_king_moves(king_pos);
         }
         if self.board.checkers.count() >= 2 {
             return std::mem::take(&mut self.moves);
         }
         pieces[Pawn].for_each(|from| self.gen_pawn_moves(from));
-        pieces[Knight].for_each(|from| self.push_squares(from, KNIGHT_MOVES[from]));
-        pieces[Bishop].for_each(|from| self.push_squares(from, self.magic.bishop_attacks(from, all_pieces)));
-        pieces[Rook].for_each(|from| self.push_squares(from, self.magic.rook_attacks(from, all_pieces)));
-        pieces[Queen].for_each(|from| self.push_squares(from, self.magic.queen_attacks(from, all_pieces)));
+        pieces[Knight].for_each(|from| self.push_squares(from, KNIGHT_MOVES[from] & self.targets(from)));
+        pieces[Bishop].for_each(|from| {
+            self.push_squares(from, self.magic.bishop_attacks(from, all_pieces) & self.targets(from));
+        });
+        pieces[Rook].for_each(|from| {
+            self.push_squares(from, self.magic.rook_attacks(from, all_pieces) & self.targets(from));
+        });
+        pieces[Queen].for_each(|from| {
+            self.push_squares(from, self.magic.queen_attacks(from, all_pieces) & self.targets(from));
+        });
 
         std::mem::take(&mut self.moves)
     }
+    /// Works out the checks and pins in the position, so only legal moves are generated.
+    fn compute_legality(&mut self) {
+        let Some(king) = self.board.active_king() else { return };
+        let side = self.board.active_side;
+        let enemy = self.board[!side];
+        let occupancy = self.board.all_pieces();
+
+        self.king_danger = self.gen_attack_map(occupancy ^ king);
+        self.check_mask = match self.board.checkers.count() {
+            0 => Bitboard::ALL,
+            1 => self.board.checkers | BETWEEN[king][self.board.checkers.bitscan().unwrap()],
+            _ => Bitboard::EMPTY,
+        };
+
+        // enemy sliders that would attack the king if the friendly pieces were removed
+        let rooks = (self.board[Rook] | self.board[Queen]) & enemy;
+        let bishops = (self.board[Bishop] | self.board[Queen]) & enemy;
+        let snipers =
+            (self.magic.rook_attacks(king, enemy) & rooks) | (self.magic.bishop_attacks(king, enemy) & bishops);
+        self.pinned = Bitboard::EMPTY;
+        snipers.for_each(|sniper| {
+            let blockers = BETWEEN[king][sniper] & occupancy;
+            if blockers.count() == 1 {
+                self.pinned |= blockers & self.board[side];
+            }
+        });
+    }
+    /// Works out the checks and pins of the position for `is_legal`.
     #[must_use]
-    #[inline]
-    pub fn is_legal(&mut self, mov: Move) -> bool {
-        if mov.flags() == MoveFlags::KingCastle || mov.flags() == MoveFlags::QueenCastle {
-            let map = self.gen_attack_map();
-            let squares = match (self.board.active_side, mov.flags() == MoveFlags::KingCastle) {
-                (Side::White, true) => [Square::F1, Square::G1],
-                (Side::White, false) => [Square::C1, Square::D1],
-                (Side::Black, true) => [Square::F8, Square::G8],
-                (Side::Black, false) => [Square::C8, Square::D8],
-            };
-            if map.contains(squares[0]) || map.contains(squares[1]) {
-                return false;
+    pub fn legality(&mut self) -> Legality {
+        self.compute_legality();
+        Legality { check_mask: self.check_mask, pinned: self.pinned, king_danger: self.king_danger }
+    }
+    /// Checks a pseudo-legal move doesn't leave the king in check, given the `legality` of the
+    /// position.
+    #[must_use]
+    pub fn is_legal(&mut self, mov: Move, legality: Legality) -> bool {
+        self.check_mask = legality.check_mask;
+        self.pinned = legality.pinned;
+        self.king_danger = legality.king_danger;
+        let Some(king) = self.board.active_king() else { return true };
+        match mov.flags() {
+            MoveFlags::KingCastle | MoveFlags::QueenCastle => {
+                self.board.checkers.is_empty() && self.can_castle(king, mov.flags().castle().unwrap())
             }
+            MoveFlags::EnPassant => self.is_legal_en_passant(mov.from(), mov.to()),
+            _ if mov.from() == king => !self.king_danger.contains(mov.to()),
+            _ => self.targets(mov.from()).contains(mov.to()),
         }
-        let unmake = self.board.make_move(mov);
-        let checkers = self.gen_checkers(!self.board.active_side);
-        self.board.unmake_move(unmake);
-        checkers.is_empty()
     }
-    // Generate attack map for enemy pieces
+    /// The squares the piece on `from` may move to without exposing the king.
     #[inline]
-    fn gen_attack_map(&self) -> Bitboard {
+    fn targets(&self, from: Square) -> Bitboard {
+        if self.pinned.contains(from) {
+            let king = self.board.active_king().unwrap();
+            self.check_mask & LINE[king][from]
+        } else {
+            self.check_mask
+        }
+    }
+    /// En passant removes two pieces from a rank at once, so the pin masks can't be trusted.
+    /// Instead, look for sliders that would see the king once the move is made.
+    fn is_legal_en_passant(&self, from: Square, to: Square) -> bool {
+        let Some(king) = self.board.active_king() else { return true };
+        let side = self.board.active_side;
+        let captured = to.add_rank(-side.forward()).unwrap();
+        let occupancy = self.board.all_pieces() ^ from ^ captured ^ to;
+        let enemy = self.board[!side];
+
+        let rooks = (self.board[Rook] | self.board[Queen]) & enemy;
+        let bishops = (self.board[Bishop] | self.board[Queen]) & enemy;
+        let sliders = (self.magic.rook_attacks(king, occupancy) & rooks)
+            | (self.magic.bishop_attacks(king, occupancy) & bishops);
+        // any other checker has to be the pawn being captured
+        let others = self.board.checkers & !(rooks | bishops) & !Bitboard::from(captured);
+        sliders.is_empty() && others.is_empty()
+    }
+    /// Generates the attack map of the enemy pieces with the given occupancy.
+    #[inline]
+    fn gen_attack_map(&self, all_pieces: Bitboard) -> Bitboard {
         let mut attacked_squares = Bitboard(0);
         let side = !self.board.active_side;
         let enemy_pieces = self.board.enemy_bitboards();
-        let all_pieces = self.board.all_pieces();
 
         enemy_pieces[Pawn].for_each(|from| attacked_squares |= ATTACK_PAWN_MOVES[side as usize][from]);
         enemy_pieces[Knight].for_each(|from| attacked_squares |= KNIGHT_MOVES[from]);
@@ -137,66 +357,53 @@ impl<'a, G: GenType> MoveGenerator<'a, G> {
         squares &= !self.board[self.board.active_side];
         squares.for_each(|sq| {
             if self.board.is_piece_at(sq) {
-                self.moves.push(Move::new(from, sq, MoveFlags::Capture));
-            } else if !G::CAPTURES_ONLY {
+                if G::CAPTURES {
+                    self.moves.push(Move::new(from, sq, MoveFlags::Capture));
+                }
+            } else if G::QUIETS {
                 self.moves.push(Move::new(from, sq, MoveFlags::Quiet));
             }
         });
     }
     fn gen_pawn_moves(&mut self, from: Square) {
         let forward = self.board.active_side.forward();
+        let targets = self.targets(from);
 
         let can_promote = (self.board.active_side == White && from.rank().0 == 6)
             || (self.board.active_side == Black && from.rank().0 == 1);
 
-        if !G::CAPTURES_ONLY {
+        if G::QUIETS {
             let to = Square::try_from(i8::from(from) + forward * 8).unwrap();
             if !self.board.is_piece_at(to) {
                 let can_double_push = (self.board.active_side == White && from.rank().0 == 1)
                     || (self.board.active_side == Black && from.rank().0 == 6);
 
-                if !can_promote {
-                    self.moves.push(Move::new(from, to, MoveFlags::Quiet));
+                if targets.contains(to) {
+                    if can_promote {
+                        self.push_promotions(from, to, false);
+                    } else {
+                        self.moves.push(Move::new(from, to, MoveFlags::Quiet));
+                    }
                 }
 
                 if can_double_push {
                     let to = Square::try_from(i8::from(from) + forward * 16).unwrap();
-                    if !self.board.is_piece_at(to) {
+                    if !self.board.is_piece_at(to) && targets.contains(to) {
                         self.moves.push(Move::new(from, to, MoveFlags::DoublePawnPush));
                     }
-                } else if can_promote {
-                    self.moves.push(Move::new(from, to, MoveFlags::QueenPromotion));
-                    self.moves.push(Move::new(from, to, MoveFlags::KnightPromotion));
-                    if !self.queen_knight_promote_only {
-                        self.moves.push(Move::new(from, to, MoveFlags::BishopPromotion));
-                        self.moves.push(Move::new(from, to, MoveFlags::RookPromotion));
-                    }
                 }
             }
         }
-        if let Some(to) = Square::try_from(i8::from(from) + forward * 8).unwrap().add_file(1) {
-            if self.board.is_side(to, !self.board.active_side) {
-                if can_promote {
-                    self.moves.push(Move::new(from, to, MoveFlags::QueenPromotionCapture));
-                    self.moves.push(Move::new(from, to, MoveFlags::KnightPromotionCapture));
-                    if !self.queen_knight_promote_only {
-                        self.moves.push(Move::new(from, to, MoveFlags::BishopPromotionCapture));
-                        self.moves.push(Move::new(from, to, MoveFlags::RookPromotionCapture));
-                    }
-                } else {
-                    self.moves.push(Move::new(from, to, MoveFlags::Capture));
-                }
-            }
+        if !G::CAPTURES {
+            return;
         }
-        if let Some(to) = Square::try_from(i8::from(from) + forward * 8).unwrap().add_file(-1) {
-            if self.board.is_side(to, !self.board.active_side) {
+        for file in [1, -1] {
+            let Some(to) = Square::try_from(i8::from(from) + forward * 8).unwrap().add_file(file) else {
+                continue;
+            };
+            if self.board.is_side(to, !self.board.active_side) && targets.contains(to) {
                 if can_promote {
-                    self.moves.push(Move::new(from, to, MoveFlags::KnightPromotionCapture));
-                    self.moves.push(Move::new(from, to, MoveFlags::QueenPromotionCapture));
-                    if !self.queen_knight_promote_only {
-                        self.moves.push(Move::new(from, to, MoveFlags::BishopPromotionCapture));
-                        self.moves.push(Move::new(from, to, MoveFlags::RookPromotionCapture));
-                    }
+                    self.push_promotions(from, to, true);
                 } else {
                     self.moves.push(Move::new(from, to, MoveFlags::Capture));
                 }
@@ -205,46 +412,69 @@ impl<'a, G: GenType> MoveGenerator<'a, G> {
         if let Some(en_passant) = self.board.en_passant_target_square {
             if ((en_passant.file().0 as i8 - from.file().0 as i8).abs()) <= 1
                 && from.rank().0 as i8 == (en_passant.rank().0 as i8 - forward)
+                && self.is_legal_en_passant(from, en_passant)
             {
                 self.moves.push(Move::new(from, en_passant, MoveFlags::EnPassant));
             }
         }
     }
+    fn push_promotions(&mut self, from: Square, to: Square, capture: bool) {
+        let flags = if capture {
+            [
+                MoveFlags::QueenPromotionCapture,
+                MoveFlags::KnightPromotionCapture,
+                MoveFlags::BishopPromotionCapture,
+                MoveFlags::RookPromotionCapture,
+            ]
+        } else {
+            [
+                MoveFlags::QueenPromotion,
+                MoveFlags::KnightPromotion,
+                MoveFlags::BishopPromotion,
+                MoveFlags::RookPromotion,
+            ]
+        };
+        let count = if self.queen_knight_promote_only { 2 } else { 4 };
+        for flags in &flags[..count] {
+            self.moves.push(Move::new(from, to, *flags));
+        }
+    }
     fn gen_king_moves(&mut self, from: Square) {
-        self.push_squares(from, KING_MOVES[from]);
-        if G::CAPTURES_ONLY || !self.board.checkers.is_empty() {
+        self.push_squares(from, KING_MOVES[from] & !self.king_danger);
+        if !G::QUIETS || !self.board.checkers.is_empty() {
             return;
         }
-        if self.board.active_side == White {
-            if self.board.can_castle.contains(CanCastle::WHITE_KING_SIDE)
-                && !self.board.is_piece_at(Square::F1)
-                && !self.board.is_piece_at(Square::G1)
-            {
-                self.moves.push(Move::new(from, Square::G1, MoveFlags::KingCastle));
-            }
-            if self.board.can_castle.contains(CanCastle::WHITE_QUEEN_SIDE)
-                && !self.board.is_piece_at(Square::C1)
-                && !self.board.is_piece_at(Square::D1)
-                && !self.board.is_piece_at(Square::B1)
+        for castle in Castle::ALL {
+            if self.board.can_castle.contains(CanCastle::new(self.board.active_side, castle))
+                && self.can_castle(from, castle)
             {
-                self.moves.push(Move::new(from, Square::C1, MoveFlags::QueenCastle));
-            }
-        } else {
-            if self.board.can_castle.contains(CanCastle::BLACK_KING_SIDE)
-                && !self.board.is_piece_at(Square::F8)
-                && !self.board.is_piece_at(Square::G8)
-            {
-                self.moves.push(Move::new(from, Square::G8, MoveFlags::KingCastle));
-            }
-            if self.board.can_castle.contains(CanCastle::BLACK_QUEEN_SIDE)
-                && !self.board.is_piece_at(Square::B8)
-                && !self.board.is_piece_at(Square::C8)
-                && !self.board.is_piece_at(Square::D8)
-            {
-                self.moves.push(Move::new(from, Square::C8, MoveFlags::QueenCastle));
+                let to = Square::new(self.board.active_side.back_rank(), castle.king_file());
+                self.moves.push(Move::new(from, to, castle.into()));
             }
         }
     }
+    /// Checks the squares the king and rook cross are empty and that the king doesn't cross an attacked square.
+    ///
+    /// The rights and the king not being in check are checked by the caller.
+    fn can_castle(&self, king: Square, castle: Castle) -> bool {
+        let side = self.board.active_side;
+        let rook = self.board.castling_rooks.square(side, castle);
+        let king_to = Square::new(side.back_rank(), castle.king_file());
+        let rook_to = Square::new(side.back_rank(), castle.rook_file());
+        if !self.board.get(side + Rook).contains(rook) {
+            return false;
+        }
+        let king_path = BETWEEN[king][king_to] | Bitboard::from(king_to);
+        let rook_path = BETWEEN[rook][rook_to] | Bitboard::from(rook_to);
+        let occupancy = self.board.all_pieces() ^ king ^ rook;
+        if !((king_path | rook_path) & occupancy).is_empty() || !(king_path & self.king_danger).is_empty() {
+            return false;
+        }
+        // in Chess960 the castling rook can be the only thing shielding the king's square from a rook
+        let enemy_rooks = (self.board[Rook] | self.board[Queen]) & self.board[!side];
+        let occupancy = occupancy ^ king_to ^ rook_to;
+        (self.magic.rook_attacks(king_to, occupancy) & enemy_rooks).is_empty()
+    }
     #[inline]
     #[must_use]
     pub fn gen_checkers(&self, side: Side) -> Bitboard {
@@ -423,3 +653,130 @@ const fn compute_num_squares_to_edge() -> [[i8; 8]; 64] {
     }
     squares
 }
+
+// only ever evaluated at compile time
+#[allow(clippy::large_stack_arrays)]
+const fn compute_between() -> [[Bitboard; 64]; 64] {
+    let mut between = [[Bitboard(0); 64]; 64];
+
+    let mut from = 0;
+    while from < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let mut ray = 0;
+            let mut step = 1;
+            while step <= NUM_SQUARES_TO_EDGE[from][direction] {
+                let to = (from as i8 + DIRECTION_OFFSETS[direction] * step) as usize;
+                between[from][to] = Bitboard(ray);
+                ray |= 1 << to;
+                step += 1;
+            }
+            direction += 1;
+        }
+        from += 1;
+    }
+    between
+}
+
+#[allow(clippy::large_stack_arrays)]
+const fn compute_lines() -> [[Bitboard; 64]; 64] {
+    let mut lines = [[Bitboard(0); 64]; 64];
+
+    let mut from = 0;
+    while from < 64 {
+        // directions come in opposite pairs
+        let mut direction = 0;
+        while direction < 8 {
+            let mut line = 1 << from;
+            let mut side = direction;
+            while side < direction + 2 {
+                let mut step = 1;
+                while step <= NUM_SQUARES_TO_EDGE[from][side] {
+                    line |= 1 << (from as i8 + DIRECTION_OFFSETS[side] * step);
+                    step += 1;
+                }
+                side += 1;
+            }
+            let mut side = direction;
+            while side < direction + 2 {
+                let mut step = 1;
+                while step <= NUM_SQUARES_TO_EDGE[from][side] {
+                    lines[from][(from as i8 + DIRECTION_OFFSETS[side] * step) as usize] = Bitboard(line);
+                    step += 1;
+                }
+                side += 1;
+            }
+            direction += 2;
+        }
+        from += 1;
+    }
+    lines
+}
+
+#[test]
+fn test_legal_moves() {
+    let positions = [
+        Board::kiwipete(),
+        Board::perft_position_3(),
+        Board::perft_position_4(),
+        Board::perft_position_5(),
+        // en passant would uncover a rook on the king
+        Board::from_fen("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").unwrap(),
+        // the pawn giving check can be taken en passant
+        Board::from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap(),
+    ];
+    for mut board in positions.clone() {
+        let mut movegen = MoveGenerator::<FullGen>::new(&mut board);
+        movegen.queen_knight_promote_only = false;
+        let legal = movegen.gen_legal_moves();
+        let legality = movegen.legality();
+        for mov in movegen.gen_pseudolegal_moves() {
+            let unmake = movegen.board.make_move(mov);
+            let leaves_check = !movegen.gen_checkers(!movegen.board.active_side).is_empty();
+            movegen.board.unmake_move(unmake);
+            assert_eq!(legal.contains(&mov), !leaves_check, "{mov} in {:?}", movegen.board);
+            assert!(movegen.board.is_pseudolegal(mov), "{mov} in {:?}", movegen.board);
+            assert_eq!(movegen.is_legal(mov, legality), !leaves_check, "{mov} in {:?}", movegen.board);
+        }
+
+        // captures and quiet moves split the legal moves between them
+        let mut captures = MoveGenerator::<CapturesOnly>::new(&mut board);
+        captures.queen_knight_promote_only = false;
+        let mut split = captures.gen_legal_moves();
+        let mut quiets = MoveGenerator::<QuietsOnly>::new(&mut board);
+        quiets.queen_knight_promote_only = false;
+        split.extend(quiets.gen_legal_moves());
+        assert_eq!(split.len(), legal.len());
+        assert!(legal.iter().all(|mov| split.contains(mov)));
+    }
+
+    // moves from other positions, as a TT move after a hash collision, are only legal if they're
+    // legal here too
+    let mut elsewhere = Moves::new();
+    for mut board in positions.clone() {
+        elsewhere.extend(board.gen_legal_moves());
+    }
+    for mut board in positions {
+        let legal = board.gen_legal_moves();
+        let mut movegen = MoveGenerator::<FullGen>::new(&mut board);
+        let legality = movegen.legality();
+        for &mov in &elsewhere {
+            let accepted = movegen.board.is_pseudolegal(mov) && movegen.is_legal(mov, legality);
+            assert_eq!(accepted, legal.contains(&mov), "{mov} in {:?}", movegen.board);
+        }
+    }
+}
+
+#[test]
+fn test_gives_check() {
+    let positions =
+        [Board::kiwipete(), Board::perft_position_3(), Board::perft_position_4(), Board::perft_position_5()];
+    for mut board in positions {
+        for mov in board.gen_legal_moves() {
+            let unmake = board.make_move(mov);
+            let in_check = board.in_check();
+            board.unmake_move(unmake);
+            assert_eq!(board.gives_check(mov), in_check, "{mov} in {board:?}");
+        }
+    }
+}
@@ -1,23 +1,46 @@
-use crate::{
-    engine::transposition::{Nodetype, TranspositionTable},
-    prelude::*,
+use std::{
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
 };
 
+use crate::prelude::*;
+
+/// A hash table of subtree sizes that perft threads share without locking.
+///
+/// Each slot stores the key xor'd with its data, so a slot torn by two threads writing at once
+/// fails the key check instead of returning another position's count.
+pub struct PerftTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+/// Counts of the moves played at one ply of a perft search, broken down by kind.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64,
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64,
+    /// Single checks given by a piece other than the one that moved. Double checks are only counted below.
+    pub discovered_checks: u64,
+    pub double_checks: u64,
+    pub checkmates: u64,
+}
+
 impl Board {
     pub fn run_perft(&mut self, depth: u8) -> u64 {
-        self.run_perft_with_table(&mut TranspositionTable::default(), depth)
+        self.run_perft_with_table(&PerftTable::default(), depth)
     }
 
-    pub fn run_perft_with_table(&mut self, table: &mut TranspositionTable, depth: u8) -> u64 {
+    pub fn run_perft_with_table(&mut self, table: &PerftTable, depth: u8) -> u64 {
         if depth == 0 {
             return 1;
-        } else if let Some(entry) = table.get_entry(self, 0, 0, depth) {
-            if depth == entry.depth {
-                return entry.treesize;
-            }
-        }
-        if depth == 1 {
+        } else if depth == 1 {
             return self.gen_legal_moves().len() as u64;
+        } else if let Some(count) = table.get(self.zobrist, depth) {
+            return count;
         }
         let mut count = 0;
         for mov in self.gen_legal_moves() {
@@ -25,9 +48,147 @@ impl Board {
             count += self.run_perft_with_table(table, depth - 1);
             self.unmake_move(unmake);
         }
-        table.insert(self, &[], depth, 0, Nodetype::Exact, count);
+        table.insert(self.zobrist, depth, count);
         count
     }
+    /// Counts the leaves below each root move, with the root moves shared out between threads.
+    /// The counts are returned in the order of `gen_legal_moves`.
+    #[must_use]
+    pub fn perft_divide(&self, depth: u8, threads: usize, table: &PerftTable) -> Vec<(Move, u64)> {
+        let moves = self.clone().gen_legal_moves();
+        let counts: Vec<AtomicU64> = moves.iter().map(|_| AtomicU64::new(0)).collect();
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..threads.clamp(1, moves.len().max(1)) {
+                let mut board = self.clone();
+                let (moves, counts, next) = (&moves, &counts, &next);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&mov) = moves.get(index) else { break };
+                    let unmake = board.make_move(mov);
+                    let count = board.run_perft_with_table(table, depth.saturating_sub(1));
+                    board.unmake_move(unmake);
+                    counts[index].store(count, Ordering::Relaxed);
+                });
+            }
+        });
+        moves.into_iter().zip(counts.into_iter().map(AtomicU64::into_inner)).collect()
+    }
+    /// Perft on several threads sharing one table. The count is the same as `run_perft`.
+    #[must_use]
+    pub fn run_perft_parallel(&self, depth: u8, threads: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let table = PerftTable::default();
+        self.perft_divide(depth, threads, &table).iter().map(|(_, count)| count).sum()
+    }
+    /// Counts the moves played at every ply up to `depth`. The last entry holds the leaves,
+    /// so its node count is the same as `run_perft(depth)`.
+    pub fn perft_stats(&mut self, depth: u8) -> Vec<PerftStats> {
+        let mut stats = vec![PerftStats::default(); depth as usize];
+        self.collect_perft_stats(&mut stats);
+        stats
+    }
+    fn collect_perft_stats(&mut self, stats: &mut [PerftStats]) {
+        let Some((ply, deeper)) = stats.split_first_mut() else { return };
+        for mov in self.gen_legal_moves() {
+            let unmake = self.make_move(mov);
+            ply.record(self, mov);
+            self.collect_perft_stats(deeper);
+            self.unmake_move(unmake);
+        }
+    }
+}
+
+impl Default for PerftTable {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl PerftTable {
+    /// Creates a table using at most `mb` megabytes, rounded down to a power of two number of slots.
+    #[must_use]
+    pub fn new(mb: usize) -> Self {
+        let num_slots = (mb * 1024 * 1024 / size_of::<[AtomicU64; 2]>()).max(1);
+        Self { slots: (0..1 << num_slots.ilog2()).map(|_| Default::default()).collect() }
+    }
+    fn slot(&self, key: Zobrist) -> &[AtomicU64; 2] {
+        &self.slots[key.int() as usize & (self.slots.len() - 1)]
+    }
+    /// The data packs the depth into the low byte and the count above it.
+    fn get(&self, key: Zobrist, depth: u8) -> Option<u64> {
+        let [check, data] = self.slot(key);
+        let data = data.load(Ordering::Relaxed);
+        let check = check.load(Ordering::Relaxed);
+        (check ^ data == key.int() && data as u8 == depth).then_some(data >> 8)
+    }
+    fn insert(&self, key: Zobrist, depth: u8, count: u64) {
+        let [check, slot] = self.slot(key);
+        let data = count << 8 | u64::from(depth);
+        check.store(key.int() ^ data, Ordering::Relaxed);
+        slot.store(data, Ordering::Relaxed);
+    }
+}
+
+impl PerftStats {
+    /// Counts a move that has just been played on the board.
+    pub fn record(&mut self, board: &mut Board, mov: Move) {
+        let flags = mov.flags();
+        self.nodes += 1;
+        self.captures += u64::from(flags.is_capture());
+        self.en_passants += u64::from(flags == MoveFlags::EnPassant);
+        self.promotions += u64::from(flags.promotion().is_some());
+
+        let mut moved = Bitboard::from(mov.to());
+        if let Some(castle) = flags.castle() {
+            self.castles += 1;
+            moved.insert(Square::new((!board.active_side).back_rank(), castle.rook_file()));
+        }
+        if board.checkers.is_empty() {
+            return;
+        }
+        self.checks += 1;
+        if board.checkers.count() > 1 {
+            self.double_checks += 1;
+        } else if !(board.checkers & !moved).is_empty() {
+            self.discovered_checks += 1;
+        }
+        self.checkmates += u64::from(board.gen_legal_moves().is_empty());
+    }
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, other: Self) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passants += other.en_passants;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.discovered_checks += other.discovered_checks;
+        self.double_checks += other.double_checks;
+        self.checkmates += other.checkmates;
+    }
+}
+
+/// Parses a line of expected perft results: a FEN, then depth and node count pairs after a `;`.
+///
+/// Both `<fen> ;D1 20 ;D2 400` as in the usual perft suites and `<fen>; 2; 400` are accepted.
+#[must_use]
+pub fn parse_perft_line(line: &str) -> Option<(Board, Vec<(u8, u64)>)> {
+    let (fen, results) = line.split_once(';')?;
+    let board = Board::from_fen(fen.trim())?;
+    let numbers = results
+        .split(|c: char| c == ';' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.trim_start_matches(['D', 'd']).parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let (pairs, []) = numbers.as_chunks::<2>() else { return None };
+    let results =
+        pairs.iter().map(|&[depth, count]| Some((u8::try_from(depth).ok()?, count))).collect::<Option<_>>()?;
+    Some((board, results))
 }
 
 #[cfg(test)]
@@ -75,4 +236,75 @@ pub(crate) mod tests {
             assert_eq!(count, result, "depth: {depth}");
         }
     }
+    #[test]
+    fn perft_parallel() {
+        assert_eq!(Board::kiwipete().run_perft_parallel(4, 4), 4_085_603);
+        assert_eq!(Board::perft_position_4().run_perft_parallel(4, 3), 422_333);
+        assert_eq!(Board::perft_position_3().run_perft_parallel(0, 2), 1);
+
+        let divide = Board::start_pos().perft_divide(3, 2, &PerftTable::new(1));
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|(_, count)| count).sum::<u64>(), 8_902);
+    }
+    #[test]
+    fn perft_stats() {
+        let stats = Board::kiwipete().perft_stats(3);
+        let expected = PerftStats {
+            nodes: 97_862,
+            captures: 17_102,
+            en_passants: 45,
+            castles: 3_162,
+            promotions: 0,
+            checks: 993,
+            discovered_checks: 0,
+            double_checks: 0,
+            checkmates: 1,
+        };
+        assert_eq!(stats[2], expected);
+        assert_eq!(stats[1].nodes, 2_039);
+
+        let stats = Board::perft_position_3().perft_stats(5);
+        let expected = PerftStats {
+            nodes: 674_624,
+            captures: 52_051,
+            en_passants: 1_165,
+            castles: 0,
+            promotions: 0,
+            checks: 52_950,
+            discovered_checks: 1_292,
+            double_checks: 3,
+            checkmates: 0,
+        };
+        assert_eq!(stats[4], expected);
+
+        let (board, results) = parse_perft_line(&format!("{} ;D1 14 ;D2 191", fen::PERFT_POSITION_3)).unwrap();
+        assert_eq!(board.to_fen(), Board::perft_position_3().to_fen());
+        assert_eq!(results, [(1, 14), (2, 191)]);
+        assert_eq!(
+            parse_perft_line(&format!("{}; 5; 4865609", fen::STARTING_FEN)).unwrap().1,
+            [(5, 4_865_609)]
+        );
+        assert!(parse_perft_line(&format!("{} ;D1", fen::STARTING_FEN)).is_none());
+    }
+    #[test]
+    fn perft_chess960() {
+        let positions: [(&str, [u64; 5]); 4] = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [1, 21, 528, 12_189, 326_672],
+            ),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [1, 20, 479, 10_471, 273_318]),
+            ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", [1, 22, 593, 13_440, 382_958]),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [1, 28, 1_120, 31_058, 1_171_749],
+            ),
+        ];
+        for (fen, results) in positions {
+            for (depth, &result) in results.iter().enumerate() {
+                let count = Board::from_fen(fen).unwrap().run_perft(depth as u8);
+                assert_eq!(count, result, "{fen} depth: {depth}");
+            }
+        }
+    }
 }
@@ -0,0 +1,489 @@
+use std::{fmt, iter::Peekable, str::FromStr};
+
+use crate::prelude::*;
+
+/// A game record: its tag pairs and the moves played from the starting position,
+/// along with any comments, annotations and variations.
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<Node>,
+    /// `1-0`, `0-1`, `1/2-1/2`, or `*` for an unfinished game.
+    pub result: String,
+}
+
+/// A move in the game tree.
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// The position the move is played in.
+    pub board: Board,
+    pub mov: Move,
+    /// Numeric annotation glyphs, e.g. 1 for `!` and 2 for `?`.
+    pub nags: Vec<u8>,
+    /// A comment before the move, which PGN only allows at the start of a line.
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    /// Alternatives to this move, each played from `board`.
+    pub variations: Vec<Vec<Node>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    UnterminatedTag,
+    UnterminatedComment,
+    InvalidFen(String),
+    IllegalMove(String),
+    InvalidNag(String),
+    UnexpectedNag,
+    UnexpectedToken(String),
+    UnbalancedVariation,
+    NoGame,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedTag => write!(f, "tag pair is missing its closing bracket"),
+            Self::UnterminatedComment => write!(f, "comment is missing its closing brace"),
+            Self::InvalidFen(fen) => write!(f, "invalid FEN tag '{fen}'"),
+            Self::IllegalMove(san) => write!(f, "illegal or ambiguous move '{san}'"),
+            Self::InvalidNag(nag) => write!(f, "invalid annotation glyph '${nag}'"),
+            Self::UnexpectedNag => write!(f, "annotation glyph before any move"),
+            Self::UnexpectedToken(token) => write!(f, "unexpected '{token}' in movetext"),
+            Self::UnbalancedVariation => write!(f, "unbalanced parentheses around a variation"),
+            Self::NoGame => write!(f, "no game found"),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl Game {
+    const RESULTS: [&'static str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+    /// Creates an empty game with the seven required tags, and a FEN tag if it doesn't start
+    /// from the standard position.
+    #[must_use]
+    pub fn new(start: Board) -> Self {
+        let mut game = Self { tags: Vec::new(), start, moves: Vec::new(), result: "*".into() };
+        for (name, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ] {
+            game.set_tag(name, value);
+        }
+        let fen = game.start.to_fen();
+        if fen != fen::STARTING_FEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        game
+    }
+    #[must_use]
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.into(),
+            None => self.tags.push((name.into(), value.into())),
+        }
+    }
+    /// Plays a move at the end of the main line.
+    ///
+    /// # Errors
+    /// Returns an error if the move isn't legal.
+    pub fn push(&mut self, mov: Move) -> Result<(), PgnError> {
+        let mut board = self.end_position();
+        if !board.gen_legal_moves().contains(&mov) {
+            return Err(PgnError::IllegalMove(mov.to_string()));
+        }
+        self.moves.push(Node::new(board, mov));
+        Ok(())
+    }
+    /// The position at the end of the main line.
+    #[must_use]
+    pub fn end_position(&self) -> Board {
+        let Some(last) = self.moves.last() else { return self.start.clone() };
+        let mut board = last.board.clone();
+        board.make_move(last.mov);
+        board
+    }
+}
+
+impl Node {
+    #[must_use]
+    pub fn new(board: Board, mov: Move) -> Self {
+        Self { board, mov, nags: Vec::new(), comment_before: None, comment: None, variations: Vec::new() }
+    }
+}
+
+/// Parses every game in a PGN file.
+///
+/// # Errors
+/// Returns the first error found in any of the games.
+pub fn parse_pgn(text: &str) -> Result<Vec<Game>, PgnError> {
+    let mut tokens = Lexer { text }.peekable();
+    let mut games = Vec::new();
+    while tokens.peek().is_some() {
+        games.push(parse_game(&mut tokens)?);
+    }
+    Ok(games)
+}
+
+impl FromStr for Game {
+    type Err = PgnError;
+    /// Parses the first game in the text.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = Lexer { text }.peekable();
+        if tokens.peek().is_none() {
+            return Err(PgnError::NoGame);
+        }
+        parse_game(&mut tokens)
+    }
+}
+
+fn parse_game(tokens: &mut Peekable<Lexer<'_>>) -> Result<Game, PgnError> {
+    let mut tags = Vec::new();
+    while let Some(Ok(Token::Tag(..))) = tokens.peek() {
+        let Some(Ok(Token::Tag(name, value))) = tokens.next() else { unreachable!() };
+        tags.push((name, value));
+    }
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen).ok_or_else(|| PgnError::InvalidFen(fen.clone()))?,
+        None => Board::start_pos(),
+    };
+    let mut result = None;
+    let moves = parse_line(tokens, start.clone(), &mut result, false)?;
+    let result =
+        result.or_else(|| tags.iter().find(|(name, _)| name == "Result").map(|(_, value)| value.clone()));
+    Ok(Game { tags, start, moves, result: result.unwrap_or_else(|| "*".into()) })
+}
+
+/// Parses moves until the end of the variation, or the end of the game for the main line.
+fn parse_line(
+    tokens: &mut Peekable<Lexer<'_>>,
+    mut board: Board,
+    result: &mut Option<String>,
+    variation: bool,
+) -> Result<Vec<Node>, PgnError> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut comment_before = None::<String>;
+    // a game without a result ends where the next one's tags begin
+    while let Some(token) = tokens.next_if(|token| !matches!(token, Ok(Token::Tag(..)))) {
+        match token? {
+            Token::Tag(..) => unreachable!("Tags are left for the next game"),
+            Token::Comment(comment) => {
+                let target = match nodes.last_mut() {
+                    Some(node) => &mut node.comment,
+                    None => &mut comment_before,
+                };
+                match target {
+                    Some(text) => {
+                        text.push(' ');
+                        text.push_str(comment);
+                    }
+                    None => *target = Some(comment.into()),
+                }
+            }
+            Token::Nag(nag) => nodes.last_mut().ok_or(PgnError::UnexpectedNag)?.nags.push(nag),
+            Token::Open => {
+                let node = nodes.last_mut().ok_or(PgnError::UnbalancedVariation)?;
+                let line = parse_line(tokens, node.board.clone(), result, true)?;
+                node.variations.push(line);
+            }
+            Token::Close if variation => return Ok(nodes),
+            Token::Close => return Err(PgnError::UnbalancedVariation),
+            Token::Symbol(symbol) if Game::RESULTS.contains(&symbol) => {
+                if variation {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                *result = Some(symbol.into());
+                return Ok(nodes);
+            }
+            Token::Symbol(symbol) => {
+                let san = strip_move_number(symbol);
+                if san.is_empty() {
+                    continue;
+                }
+                let (san, suffix) = san.split_at(san.find(['!', '?']).unwrap_or(san.len()));
+                let mov = board.parse_san(san).ok_or_else(|| PgnError::IllegalMove(san.into()))?;
+                let mut node = Node::new(board.clone(), mov);
+                node.nags.extend(suffix_nag(suffix));
+                node.comment_before = comment_before.take();
+                nodes.push(node);
+                board.make_move(mov);
+            }
+        }
+    }
+    if variation {
+        Err(PgnError::UnbalancedVariation)
+    } else {
+        Ok(nodes)
+    }
+}
+
+/// Strips a move number such as `12.` or `12...`, which may be written without a space before the move.
+fn strip_move_number(symbol: &str) -> &str {
+    let rest = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.starts_with('.') {
+        rest.trim_start_matches('.')
+    } else {
+        symbol
+    }
+}
+
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return None,
+    })
+}
+
+enum Token<'a> {
+    Tag(String, String),
+    Comment(&'a str),
+    Nag(u8),
+    Open,
+    Close,
+    Symbol(&'a str),
+}
+
+struct Lexer<'a> {
+    text: &'a str,
+}
+
+impl<'a> Lexer<'a> {
+    fn take_until(&mut self, end: impl Fn(char) -> bool) -> &'a str {
+        let index = self.text.find(end).unwrap_or(self.text.len());
+        let (taken, rest) = self.text.split_at(index);
+        self.text = rest;
+        taken
+    }
+    fn tag(&mut self) -> Result<Token<'a>, PgnError> {
+        let name = self.take_until(|c| c.is_whitespace() || c == '"' || c == ']').to_string();
+        self.text = self.text.trim_start();
+        let mut value = String::new();
+        if let Some(rest) = self.text.strip_prefix('"') {
+            let mut chars = rest.char_indices();
+            loop {
+                match chars.next().ok_or(PgnError::UnterminatedTag)? {
+                    (_, '\\') => value.extend(chars.next().map(|(_, c)| c)),
+                    (index, '"') => {
+                        self.text = &rest[index + 1..];
+                        break;
+                    }
+                    (_, c) => value.push(c),
+                }
+            }
+        }
+        self.take_until(|c| c == ']');
+        self.text = self.text.strip_prefix(']').ok_or(PgnError::UnterminatedTag)?;
+        Ok(Token::Tag(name, value))
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, PgnError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let trimmed = self.text.trim_start();
+            let skipped = &self.text[..self.text.len() - trimmed.len()];
+            // lines starting with % are escaped from PGN processing
+            if trimmed.starts_with('%') && (skipped.is_empty() || skipped.contains('\n')) {
+                self.text = trimmed;
+                self.take_until(|c| c == '\n');
+                continue;
+            }
+            self.text = trimmed;
+            break;
+        }
+        let mut chars = self.text.chars();
+        let first = chars.next()?;
+        let rest = chars.as_str();
+        Some(Ok(match first {
+            '[' => {
+                self.text = rest;
+                return Some(self.tag());
+            }
+            '{' => {
+                let Some(end) = rest.find('}') else {
+                    self.text = "";
+                    return Some(Err(PgnError::UnterminatedComment));
+                };
+                self.text = &rest[end + 1..];
+                Token::Comment(rest[..end].trim())
+            }
+            '}' => {
+                self.text = rest;
+                return Some(Err(PgnError::UnexpectedToken(first.into())));
+            }
+            ';' => {
+                self.text = rest;
+                Token::Comment(self.take_until(|c| c == '\n').trim())
+            }
+            '(' => {
+                self.text = rest;
+                Token::Open
+            }
+            ')' => {
+                self.text = rest;
+                Token::Close
+            }
+            '$' => {
+                self.text = rest;
+                let nag = self.take_until(|c| !c.is_ascii_digit());
+                let Ok(nag) = nag.parse() else {
+                    return Some(Err(PgnError::InvalidNag(nag.into())));
+                };
+                Token::Nag(nag)
+            }
+            _ => Token::Symbol(self.take_until(|c| c.is_whitespace() || "{}();[$".contains(c))),
+        }))
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{name} \"{}\"]", value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        write_line(&self.moves, &mut tokens);
+        tokens.push(self.result.clone());
+
+        // export format keeps lines under 80 characters, breaking only between tokens
+        let mut width = 0;
+        for token in tokens {
+            if width > 0 && width + 1 + token.len() >= 80 {
+                writeln!(f)?;
+                width = 0;
+            } else if width > 0 {
+                write!(f, " ")?;
+                width += 1;
+            }
+            write!(f, "{token}")?;
+            width += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+fn write_line(nodes: &[Node], tokens: &mut Vec<String>) {
+    // black's moves only need a number at the start of a line or after an interruption
+    let mut needs_number = true;
+    for node in nodes {
+        if let Some(comment) = &node.comment_before {
+            tokens.push(format!("{{{comment}}}"));
+        }
+        let number = node.board.fullmove_counter;
+        if node.board.active_side == White {
+            tokens.push(format!("{number}."));
+        } else if needs_number || node.comment_before.is_some() {
+            tokens.push(format!("{number}..."));
+        }
+        tokens.push(node.board.clone().move_to_san(node.mov));
+        tokens.extend(node.nags.iter().map(|nag| format!("${nag}")));
+        if let Some(comment) = &node.comment {
+            tokens.push(format!("{{{comment}}}"));
+        }
+        needs_number = node.comment.is_some();
+
+        for variation in &node.variations {
+            let start = tokens.len();
+            write_line(variation, tokens);
+            if let Some(first) = tokens.get_mut(start) {
+                first.insert(0, '(');
+                tokens.last_mut().expect("Variation has tokens").push(')');
+                needs_number = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+const GAME: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Result "1-0"]
+
+% an escaped line
+{Opening comment} 1. e4 e5 2. Nf3!? Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4) exd4) 3. Bb5 $1 a6
+; rest of line comment
+4.Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 1-0
+
+[Event "Second"]
+[SetUp "1"]
+[FEN "6k1/5ppp/8/8/8/8/8/R3K3 w Q - 0 1"]
+
+1. O-O-O?? Kf8 *"#;
+
+#[test]
+fn test_parse_pgn() {
+    let games = parse_pgn(GAME).unwrap();
+    assert_eq!(games.len(), 2);
+
+    let game = &games[0];
+    assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+    assert_eq!(game.result, "1-0");
+    assert_eq!(game.moves.len(), 16);
+    assert_eq!(game.moves[0].comment_before.as_deref(), Some("Opening comment"));
+    assert_eq!(game.moves[2].nags, [5]);
+    assert_eq!(game.moves[4].nags, [1]);
+    assert_eq!(game.moves[5].comment.as_deref(), Some("rest of line comment"));
+
+    let variation = &game.moves[3].variations[0];
+    assert_eq!(variation.len(), 3);
+    assert_eq!(variation[0].comment.as_deref(), Some("Philidor"));
+    assert_eq!(variation[1].variations[0][0].mov.to_string(), "f1c4");
+    assert_eq!(
+        game.end_position().to_fen(),
+        "r1bq1rk1/2p1bppp/p1np1n2/1p2p3/4P3/1BP2N2/PP1P1PPP/RNBQR1K1 w - - 1 9"
+    );
+
+    let game = &games[1];
+    assert_eq!(game.start.to_fen(), "6k1/5ppp/8/8/8/8/8/R3K3 w Q - 0 1");
+    assert_eq!(game.moves[0].nags, [4]);
+    assert_eq!(game.result, "*");
+
+    assert!(matches!("1. e4 e5 2. Ke3".parse::<Game>(), Err(PgnError::IllegalMove(_))));
+    assert!(matches!("1. e4 (1. d4".parse::<Game>(), Err(PgnError::UnbalancedVariation)));
+    assert!(matches!("1. e4 {oops".parse::<Game>(), Err(PgnError::UnterminatedComment)));
+    assert!(matches!(parse_pgn("1. e4 } e5"), Err(PgnError::UnexpectedToken(token)) if token == "}"));
+    assert!(matches!("$1 1. e4".parse::<Game>(), Err(PgnError::UnexpectedNag)));
+    assert!(matches!("1. e4 $300".parse::<Game>(), Err(PgnError::InvalidNag(_))));
+}
+
+#[test]
+fn test_write_pgn() {
+    for game in parse_pgn(GAME).unwrap() {
+        let written = game.to_string();
+        assert!(written.lines().all(|line| line.len() < 80));
+        let reparsed: Game = written.parse().unwrap();
+        assert_eq!(reparsed.to_string(), written);
+    }
+    let written = parse_pgn(GAME).unwrap()[0].to_string();
+    assert!(written.starts_with("[Event \"Casual \\\"blitz\\\"\"]\n[Site \"?\"]\n[Result \"1-0\"]\n\n"));
+    assert!(written.replace('\n', " ").contains(
+        "{Opening comment} 1. e4 e5 2. Nf3 $5 Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4) 3... exd4) 3. Bb5 $1"
+    ));
+
+    let mut game = Game::new(Board::start_pos());
+    let mov = Move::new(Square::E2, Square::E4, MoveFlags::DoublePawnPush);
+    assert_eq!(game.push(mov), Ok(()));
+    assert!(game.push(mov).is_err());
+    assert_eq!(game.to_string().lines().last(), Some("1. e4 *"));
+    assert_eq!(game.tag("Date"), Some("????.??.??"));
+}
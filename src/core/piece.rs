@@ -86,7 +86,7 @@ impl Piece {
         };
         if self.is_white() {
             symbol.make_ascii_uppercase();
-        };
+        }
         symbol
     }
     #[must_use]
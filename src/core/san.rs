@@ -0,0 +1,179 @@
+use crate::prelude::*;
+
+impl Board {
+    /// Writes a legal move in Standard Algebraic Notation, e.g. `Nbd7`, `exd5`, `e8=Q+` or `O-O-O#`.
+    ///
+    /// # Panics
+    /// Panics if the move isn't legal in this position.
+    #[must_use]
+    pub fn move_to_san(&mut self, mov: Move) -> String {
+        let mut san = String::new();
+        if let Some(castle) = mov.flags().castle() {
+            san.push_str(if castle == Castle::KingSide { "O-O" } else { "O-O-O" });
+        } else {
+            let kind = self.get_square_kind(mov.from()).expect("The move should be legal");
+            if kind == Pawn {
+                if mov.flags().is_capture() {
+                    san.push(file_symbol(mov.from().file()));
+                }
+            } else {
+                san.push((White + kind).symbol());
+                san.push_str(&self.disambiguation(mov, kind));
+            }
+            if mov.flags().is_capture() {
+                san.push('x');
+            }
+            san.push_str(mov.to().algebraic());
+            if let Some(promotion) = mov.flags().promotion() {
+                san.push('=');
+                san.push((White + PieceKind::from(promotion)).symbol());
+            }
+        }
+
+        let unmake = self.make_move(mov);
+        if self.in_check() {
+            san.push(if self.gen_legal_moves().is_empty() { '#' } else { '+' });
+        }
+        self.unmake_move(unmake);
+        san
+    }
+    /// Adds the file, rank or both of the starting square when another piece of the same kind
+    /// could also move to the target square.
+    fn disambiguation(&mut self, mov: Move, kind: PieceKind) -> String {
+        let others: Moves = self
+            .gen_legal_moves()
+            .into_iter()
+            .filter(|other| other.to() == mov.to() && other.from() != mov.from())
+            .filter(|other| self.get_square_kind(other.from()) == Some(kind))
+            .collect();
+        let from = mov.from();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.from().file() != from.file()) {
+            file_symbol(from.file()).to_string()
+        } else if others.iter().all(|other| other.from().rank() != from.rank()) {
+            rank_symbol(from.rank()).to_string()
+        } else {
+            from.algebraic().to_string()
+        }
+    }
+    /// Finds the legal move written in Standard Algebraic Notation.
+    ///
+    /// Check, mate and annotation suffixes are ignored, and `0-0` is accepted for castling.
+    /// Returns `None` when no legal move matches or the notation is ambiguous.
+    #[must_use]
+    pub fn parse_san(&mut self, san: &str) -> Option<Move> {
+        let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let castle = match san {
+            "O-O" | "0-0" => Some(Castle::KingSide),
+            "O-O-O" | "0-0-0" => Some(Castle::QueenSide),
+            _ => None,
+        };
+        if let Some(castle) = castle {
+            return self.gen_legal_moves().into_iter().find(|mov| mov.flags().castle() == Some(castle));
+        }
+
+        let mut san = san.as_bytes();
+        let kind = match san.first()? {
+            b'N' => Knight,
+            b'B' => Bishop,
+            b'R' => Rook,
+            b'Q' => Queen,
+            b'K' => King,
+            _ => Pawn,
+        };
+        if kind != Pawn {
+            san = &san[1..];
+        }
+        let promotion = match san {
+            [rest @ .., b'=', symbol] | [rest @ .., symbol @ (b'N' | b'B' | b'R' | b'Q')] => {
+                san = rest;
+                Some(match symbol {
+                    b'N' => Promotion::Knight,
+                    b'B' => Promotion::Bishop,
+                    b'R' => Promotion::Rook,
+                    b'Q' => Promotion::Queen,
+                    _ => return None,
+                })
+            }
+            _ => None,
+        };
+        let [rest @ .., file, rank] = san else { return None };
+        let to: Square = std::str::from_utf8(&[*file, *rank]).ok()?.parse().ok()?;
+        let rest = rest.strip_suffix(b"x").unwrap_or(rest);
+        let (from_file, from_rank) = match rest {
+            [] => (None, None),
+            [file @ b'a'..=b'h'] => (Some(File(file - b'a')), None),
+            [rank @ b'1'..=b'8'] => (None, Some(Rank(rank - b'1'))),
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => (Some(File(file - b'a')), Some(Rank(rank - b'1'))),
+            _ => return None,
+        };
+        // pawns only leave their file when capturing, which names the file they came from
+        let from_file = if kind == Pawn { from_file.or(Some(to.file())) } else { from_file };
+
+        let mut candidates = self.gen_legal_moves().into_iter().filter(|mov| {
+            mov.to() == to
+                && mov.flags().castle().is_none()
+                && mov.flags().promotion() == promotion
+                && self.get_square_kind(mov.from()) == Some(kind)
+                && from_file.is_none_or(|file| mov.from().file() == file)
+                && from_rank.is_none_or(|rank| mov.from().rank() == rank)
+        });
+        let mov = candidates.next()?;
+        candidates.next().is_none().then_some(mov)
+    }
+}
+
+fn file_symbol(file: File) -> char {
+    (b'a' + file.0) as char
+}
+
+fn rank_symbol(rank: Rank) -> char {
+    (b'1' + rank.0) as char
+}
+
+#[test]
+fn test_san() {
+    let mut board = Board::from_fen("r3k2r/1P6/8/3N1N2/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    for (san, uci) in
+        [("O-O", "e1g1"), ("O-O-O", "e1c1"), ("Nde3", "d5e3"), ("bxa8=Q+", "b7a8q"), ("b8=N", "b7b8n")]
+    {
+        let mov = board.find_legal_move(uci.parse().unwrap()).unwrap();
+        assert_eq!(board.move_to_san(mov), san);
+        assert_eq!(board.parse_san(san), Some(mov));
+    }
+    assert_eq!(board.parse_san("Ne3"), None, "ambiguous");
+    assert_eq!(board.parse_san("Nd5e3+"), board.parse_san("Nde3"));
+    assert_eq!(board.parse_san("bxa8Q"), board.find_legal_move("b7a8q".parse().unwrap()));
+
+    let mut board = Board::from_fen("4k3/8/8/8/8/3p4/4P3/4K3 w - - 0 1").unwrap();
+    assert_eq!(board.parse_san("e3").map(|mov| mov.to_string()), Some("e2e3".into()));
+    assert_eq!(board.parse_san("exd3").map(|mov| mov.to_string()), Some("e2d3".into()));
+
+    let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let mate = board.parse_san("Ra8").unwrap();
+    assert_eq!(board.move_to_san(mate), "Ra8#");
+}
+
+#[test]
+fn test_san_round_trip() {
+    let positions = [
+        Board::start_pos(),
+        Board::kiwipete(),
+        Board::perft_position_3(),
+        Board::perft_position_4(),
+        Board::perft_position_5(),
+    ];
+    for mut board in positions {
+        for mov in board.gen_legal_moves() {
+            let unmake = board.make_move(mov);
+            for reply in board.gen_legal_moves() {
+                let san = board.move_to_san(reply);
+                assert_eq!(board.parse_san(&san), Some(reply), "{san} in {board:?}");
+            }
+            board.unmake_move(unmake);
+            let san = board.move_to_san(mov);
+            assert_eq!(board.parse_san(&san), Some(mov), "{san} in {board:?}");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use movegen::{ATTACK_PAWN_MOVES, KING_MOVES, KNIGHT_MOVES};
+
+use super::magic::Magic;
+use crate::prelude::*;
+
+/// Piece values used to weigh exchanges. The king is never captured, but it mustn't look cheap.
+const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20_000];
+
+impl Board {
+    /// The pieces of either side attacking `sq`, with sliders seeing through the squares missing
+    /// from `occupancy`.
+    #[must_use]
+    pub fn attackers_to(&self, sq: Square, occupancy: Bitboard) -> Bitboard {
+        let magic = Magic::get();
+        let pawns = (ATTACK_PAWN_MOVES[Black as usize][sq] & self[White])
+            | (ATTACK_PAWN_MOVES[White as usize][sq] & self[Black]);
+        let attackers = (pawns & self[Pawn])
+            | (KNIGHT_MOVES[sq] & self[Knight])
+            | (KING_MOVES[sq] & self[King])
+            | (magic.bishop_attacks(sq, occupancy) & (self[Bishop] | self[Queen]))
+            | (magic.rook_attacks(sq, occupancy) & (self[Rook] | self[Queen]));
+        attackers & occupancy
+    }
+    /// Static Exchange Evaluation: the material the side to move wins, in centipawns, if both
+    /// sides keep recapturing on the target square with their least valuable piece for as long as
+    /// it pays. Pieces behind the capturers join in as they are uncovered. Pins are ignored.
+    #[must_use]
+    pub fn see(&self, mov: Move) -> i32 {
+        let to = mov.to();
+        let mut occupancy = self.all_pieces() ^ mov.from();
+        let mut gain = [0; 32];
+        gain[0] = if mov.flags() == MoveFlags::EnPassant {
+            occupancy ^= to.add_rank(-self.active_side.forward()).expect("En passant captures a pawn");
+            SEE_VALUES[Pawn as usize]
+        } else if mov.flags().is_capture() {
+            // a Chess960 king can castle onto its own rook's square, but castling isn't a capture
+            self.get_square_kind(to).map_or(0, |kind| SEE_VALUES[kind as usize])
+        } else {
+            0
+        };
+        let mut on_square = self.get_square_kind(mov.from()).expect("The move should have a piece to move");
+        if let Some(promotion) = mov.flags().promotion() {
+            on_square = PieceKind::from(promotion);
+            gain[0] += SEE_VALUES[on_square as usize] - SEE_VALUES[Pawn as usize];
+        }
+
+        let mut side = !self.active_side;
+        let mut depth = 0;
+        while depth + 1 < gain.len() {
+            let attackers = self.attackers_to(to, occupancy);
+            let Some(kind) =
+                PieceKind::ALL.into_iter().find(|&kind| !(attackers & self[side] & self[kind]).is_empty())
+            else {
+                break;
+            };
+            // the king can only take last
+            if kind == King && !(attackers & self[!side]).is_empty() {
+                break;
+            }
+            depth += 1;
+            gain[depth] = SEE_VALUES[on_square as usize] - gain[depth - 1];
+            let from = (attackers & self[side] & self[kind]).bitscan().unwrap();
+            occupancy ^= from;
+            on_square = kind;
+            side = !side;
+        }
+        // either side can stop recapturing when carrying on would lose material
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+}
+
+#[test]
+fn test_see() {
+    let see = |fen: &str, uci: &str| {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mov = board.find_legal_move(uci.parse().unwrap()).unwrap();
+        board.see(mov)
+    };
+    // an undefended pawn, then one defended by a pawn
+    assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - -", "e1e5"), 100);
+    assert_eq!(see("1k1r4/1pp4p/p4p2/4p3/8/P5P1/1PP4P/2K1R3 w - -", "e1e5"), -400);
+    // the rook behind the queen joins in once the queen has taken
+    assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - -", "d3e5"), -200);
+    assert_eq!(see("4k3/8/8/3p4/4P3/8/8/4K3 w - -", "e4d5"), 100);
+    assert_eq!(see("4k3/8/2p5/3p4/4P3/8/8/3QK3 w - -", "d1d5"), -700);
+    assert_eq!(see("3rk3/8/8/3p4/8/8/3R4/3RK3 w - -", "d2d5"), 100);
+    // the king can only recapture when nothing else defends
+    assert_eq!(see("8/8/4k3/3p4/8/8/8/3RK3 w - -", "d1d5"), -400);
+    assert_eq!(see("8/8/4k3/3p4/8/8/6B1/3RK3 w - -", "d1d5"), 100);
+    assert_eq!(see("4k3/8/8/8/8/8/3P4/4K3 w - -", "d2d4"), 0);
+    assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - -", "a7b8q"), 1300);
+    assert_eq!(see("4k3/8/8/2pP4/8/8/8/4K3 w - c6", "d5c6"), 100);
+}
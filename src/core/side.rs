@@ -1,5 +1,7 @@
 use core::ops::Not;
 
+use crate::prelude::Rank;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
 pub enum Side {
@@ -26,6 +28,15 @@ impl Side {
             Self::White => 1,
         }
     }
+    /// The rank the pieces start on.
+    #[must_use]
+    #[inline]
+    pub const fn back_rank(self) -> Rank {
+        match self {
+            Self::Black => Rank(7),
+            Self::White => Rank(0),
+        }
+    }
     /// What is considered a beneficial score for this side
     #[inline]
     #[must_use]
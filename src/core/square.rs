@@ -365,7 +365,6 @@ macro_rules! impl_into {
     (@single $int: ident) => {
         impl From<Square> for $int {
             #[allow(clippy::cast_possible_wrap)]
-            #[must_use]
             #[inline]
             fn from(square: Square) -> $int {
                 square.int() as $int
@@ -7,6 +7,11 @@ pub struct Zobrist(u64);
 
 impl Zobrist {
     pub const DEFAULT: Self = Self(0);
+    #[must_use]
+    #[inline]
+    pub fn int(self) -> u64 {
+        self.0
+    }
     #[inline]
     pub fn xor_side_to_move(&mut self) {
         self.0 ^= SIDE_KEY;
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use super::SearchLimits;
+use crate::{core::epd::Epd, prelude::*};
+
+/// The depth `bench` searches to when given neither a depth nor a time.
+pub const BENCH_DEPTH: u8 = 6;
+
+/// Positions searched by `bench` when no EPD file is given. Changing them changes the signature.
+pub const BENCH_POSITIONS: [&str; 14] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - id \"startpos\";",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - id \"kiwipete\";",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - id \"perft.3\";",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - id \"perft.4\";",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - hmvc 1; fmvn 8; id \"perft.5\";",
+    "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 hmvc 0; fmvn 14; id \"middlegame.1\";",
+    "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - hmvc 2; fmvn 24; id \"middlegame.2\";",
+    "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - hmvc 16; fmvn 42; id \"middlegame.3\";",
+    "6k1/1R3p2/6p1/2Bp3n/3P4/1r4P1/5P2/6K1 b - - hmvc 3; fmvn 46; id \"endgame.1\";",
+    "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+    "8/7p/5k2/5p2/p1p2P2/Pr1pPK2/1P1R3P/8 b - - bm Rxb2; id \"WAC.002\";",
+    "5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - bm Rg3; id \"WAC.003\";",
+    "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - bm Qxh7+; id \"WAC.004\";",
+    "5k2/6pp/p1qN4/1p1p4/3P4/2PKP2Q/PP3r2/3R4 b - - bm Qc4+; id \"WAC.005\";",
+];
+
+/// How the best move found for a position measures up to its `bm` and `am` opcodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Solved,
+    Unsolved,
+    /// The `bm` or `am` moves aren't legal moves in SAN, so the position can't be judged.
+    Invalid,
+}
+
+/// The outcome of searching one position of a test suite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    pub best_move: Move,
+    /// Whether the best move satisfies the `bm` and `am` opcodes, if the position has either.
+    pub verdict: Option<Verdict>,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+impl Engine {
+    /// Searches each position as if it started a new game, so the node counts only depend on the
+    /// positions and limits. With a fixed depth and one thread their total is a signature of the
+    /// search. This clears the position, table and history, so the `bench` command runs it on an engine of its own.
+    pub fn bench(
+        &mut self,
+        positions: &[Epd],
+        limits: SearchLimits,
+        time_available: Duration,
+    ) -> Vec<BenchResult> {
+        positions
+            .iter()
+            .map(|epd| {
+                self.new_game();
+                self.board = epd.board.clone();
+                self.seen_positions = vec![self.board.zobrist];
+                self.searchmoves = None;
+                self.limits = limits;
+                self.time_available = time_available;
+
+                let start = Instant::now();
+                let best_move = self.search();
+                let has_answer = epd.operation("bm").is_some() || epd.operation("am").is_some();
+                BenchResult {
+                    best_move,
+                    verdict: has_answer.then(|| match epd.is_solved_by(best_move) {
+                        Some(true) => Verdict::Solved,
+                        Some(false) => Verdict::Unsolved,
+                        None => Verdict::Invalid,
+                    }),
+                    nodes: self.total_nodes,
+                    time: start.elapsed(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_bench() {
+    let positions: Vec<Epd> = BENCH_POSITIONS.iter().map(|line| Epd::parse(line).unwrap()).collect();
+    assert_eq!(positions[4].board.to_fen(), fen::PERFT_POSITION_5);
+
+    let limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+    let mut engine = Engine::new(Board::start_pos());
+    engine.quiet = true;
+    let results = engine.bench(&positions[8..], limits, Duration::MAX);
+    assert_eq!(results[0].verdict, None);
+    assert_eq!(results[1].verdict, Some(Verdict::Solved), "WAC.001 is a mate in two");
+
+    // a signature that changes between runs would be useless
+    let nodes: Vec<u64> = results.iter().map(|result| result.nodes).collect();
+    let again = engine.bench(&positions[8..], limits, Duration::MAX);
+    assert_eq!(again.iter().map(|result| result.nodes).collect::<Vec<_>>(), nodes);
+
+    // an answer that isn't a legal move makes the position invalid rather than solved by anything
+    let broken = Epd::parse(&BENCH_POSITIONS[9].replace("Qg6", "Qz9")).unwrap();
+    let results = engine.bench(&[broken], SearchLimits { depth: Some(1), ..limits }, Duration::MAX);
+    assert_eq!(results[0].verdict, Some(Verdict::Invalid));
+}
@@ -9,6 +9,7 @@ impl Engine {
     #[allow(clippy::too_many_lines)]
     pub fn raw_evaluation(&mut self) -> i32 {
         let phase = self.phase();
+        let weights = self.options.eval;
 
         if !self.sufficient_material_to_force_checkmate() {
             return 0;
@@ -36,7 +37,7 @@ impl Engine {
             // punish double pawns
             for file in 0..8 {
                 let pawns_in_file = (friendly[Pawn] & (File(file).mask())).count() as i32;
-                total -= (pawns_in_file - 1).max(0) * 25;
+                total -= (pawns_in_file - 1).max(0) * weights.doubled_pawn;
             }
             // reward non-isolated pawns
             friendly[Pawn].for_each(|sq| {
@@ -74,7 +75,7 @@ impl Engine {
                 }
                 let is_outpost = (sq.outpost_mask(side) & enemy[Pawn]).is_empty();
                 if is_outpost {
-                    total += 20;
+                    total += weights.outpost;
                 }
             });
             // reward pawns close to king
@@ -89,9 +90,9 @@ impl Engine {
             // reward rooks on an open file
             friendly[Rook].for_each(|sq| {
                 if (self.board[Pawn] & sq.file().mask()).is_empty() {
-                    total += 20;
+                    total += weights.rook_open_file;
                 } else if (friendly[Pawn] & (sq.file().mask())).is_empty() {
-                    total += 10;
+                    total += weights.rook_open_file / 2;
                 }
             });
             // reward rooks able to see eachother
@@ -102,7 +103,7 @@ impl Engine {
                 }
             }
             // reward bishop pair
-            total += self.has_bishop_pair(side) as i32 * 50;
+            total += self.has_bishop_pair(side) as i32 * weights.bishop_pair;
             // material and piece square table values
             for piecekind in [Pawn, Knight, Bishop, Rook, Queen] {
                 let piece = side + piecekind;
@@ -130,7 +131,7 @@ impl Engine {
                 final_total += mop_up_score * phase.endgame();
             }
         }
-        let mobility_score = self.raw_mobility_eval();
+        let mobility_score = self.raw_mobility_eval() * weights.mobility / 100;
         final_total + mobility_score
     }
     #[inline]
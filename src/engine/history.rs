@@ -0,0 +1,139 @@
+use crate::prelude::*;
+
+/// History scores stay within this far of zero: bonuses shrink as a score gets close to it.
+const MAX_HISTORY: i32 = 16_384;
+/// The largest bonus or malus a single cutoff gives.
+const MAX_BONUS: i32 = 1200;
+
+/// What the search has learned about quiet moves from beta cutoffs, used to order them.
+pub struct History {
+    /// Indexed by side, from and to square.
+    butterfly: Box<[[[i16; 64]; 64]; 2]>,
+    /// Indexed by the piece and target square of an earlier move, see `continuation_index`, then
+    /// by those of the move itself.
+    continuation: Box<[[[i16; 64]; 12]]>,
+    /// The reply that last refuted a move, indexed by the piece and target square of that move.
+    countermoves: [[Option<Move>; 64]; 12],
+    /// The two quiet moves that most recently caused a cutoff at each ply.
+    killers: Vec<[Option<Move>; 2]>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            butterfly: Box::new([[[0; 64]; 64]; 2]),
+            continuation: vec![[[0; 64]; 12]; 12 * 64].into_boxed_slice(),
+            countermoves: [[None; 64]; 12],
+            killers: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        *self.butterfly = [[[0; 64]; 64]; 2];
+        self.continuation.fill([[0; 64]; 12]);
+        self.countermoves = [[None; 64]; 12];
+        self.killers.clear();
+    }
+    #[must_use]
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers.get(ply).copied().unwrap_or_default()
+    }
+    #[must_use]
+    pub fn countermove(&self, previous: Option<(Piece, Move)>) -> Option<Move> {
+        let (piece, mov) = previous?;
+        self.countermoves[piece as usize][mov.to()]
+    }
+    /// The butterfly score of a quiet move plus its continuation scores after each earlier move.
+    #[must_use]
+    pub fn score(&self, piece: Piece, mov: Move, earlier: &[Option<(Piece, Move)>]) -> i32 {
+        let butterfly = self.butterfly[piece.side() as usize][mov.from()][mov.to()];
+        let continuation = earlier.iter().flatten().map(|&(earlier_piece, earlier_move)| {
+            let earlier = continuation_index(earlier_piece, earlier_move);
+            i32::from(self.continuation[earlier][piece as usize][mov.to()])
+        });
+        i32::from(butterfly) + continuation.sum::<i32>()
+    }
+    fn add(&mut self, piece: Piece, mov: Move, earlier: &[Option<(Piece, Move)>], bonus: i32) {
+        gravity(&mut self.butterfly[piece.side() as usize][mov.from()][mov.to()], bonus);
+        for &(earlier_piece, earlier_move) in earlier.iter().flatten() {
+            let earlier = continuation_index(earlier_piece, earlier_move);
+            let entry = &mut self.continuation[earlier][piece as usize][mov.to()];
+            gravity(entry, bonus);
+        }
+    }
+}
+
+fn continuation_index(piece: Piece, mov: Move) -> usize {
+    piece as usize * 64 + usize::from(mov.to())
+}
+
+/// Moves a score towards `±MAX_HISTORY` by less the closer it already is.
+fn gravity(entry: &mut i16, bonus: i32) {
+    let score = i32::from(*entry);
+    *entry = (score + bonus - score * bonus.abs() / MAX_HISTORY) as i16;
+}
+
+impl Engine {
+    /// The moves made one and two plies before the current position, if they were made in the search.
+    pub(crate) fn earlier_moves(&self) -> [Option<(Piece, Move)>; 2] {
+        let mut earlier = self.played.iter().rev().copied();
+        [earlier.next().flatten(), earlier.next().flatten()]
+    }
+    /// Rewards the quiet move that caused a beta cutoff and penalises the quiet moves tried before it.
+    pub(crate) fn update_history(&mut self, best: Move, tried: &[Move], depth: u8) {
+        let bonus = (16 * i32::from(depth) * i32::from(depth)).min(MAX_BONUS);
+        let earlier = self.earlier_moves();
+        for &mov in tried {
+            let piece = self.board.get_square(mov.from()).unwrap();
+            self.history.add(piece, mov, &earlier, -bonus);
+        }
+        let piece = self.board.get_square(best.from()).unwrap();
+        self.history.add(piece, best, &earlier, bonus);
+
+        let ply = self.depth_from_root as usize;
+        if self.history.killers.len() <= ply {
+            self.history.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.history.killers[ply];
+        if killers[0] != Some(best) {
+            *killers = [Some(best), killers[0]];
+        }
+        if let Some((piece, mov)) = earlier[0] {
+            self.history.countermoves[piece as usize][mov.to()] = Some(best);
+        }
+    }
+}
+
+#[test]
+fn test_history() {
+    let mut engine = Engine::new(Board::start_pos());
+    let [e4, d4, nf3] =
+        ["e2e4", "d2d4", "g1f3"].map(|mov| engine.board.find_legal_move(mov.parse().unwrap()).unwrap());
+    for depth in 1..=20 {
+        engine.update_history(e4, &[d4, nf3], depth);
+    }
+    let score = |engine: &Engine, mov: Move| engine.history.score(WhitePawn, mov, &[]);
+    assert!(score(&engine, e4) > 0 && score(&engine, e4) <= MAX_HISTORY);
+    assert!(score(&engine, d4) < 0 && score(&engine, d4) >= -MAX_HISTORY);
+    assert_eq!(engine.history.killers(0), [Some(e4), None]);
+    engine.update_history(d4, &[], 1);
+    assert_eq!(engine.history.killers(0), [Some(d4), Some(e4)]);
+
+    // the reply to e4 is remembered as its countermove, along with how it did after e4
+    let unmake = engine.board.make_move(e4);
+    engine.played.push(Some((WhitePawn, e4)));
+    engine.depth_from_root = 1;
+    let e5 = engine.board.find_legal_move("e7e5".parse().unwrap()).unwrap();
+    engine.update_history(e5, &[], 10);
+    assert_eq!(engine.history.countermove(Some((WhitePawn, e4))), Some(e5));
+    let earlier = engine.earlier_moves();
+    assert!(engine.history.score(BlackPawn, e5, &earlier) > engine.history.score(BlackPawn, e5, &[]));
+    engine.board.unmake_move(unmake);
+
+    engine.new_game();
+    assert_eq!(score(&engine, e4), 0);
+    assert_eq!(engine.history.killers(1), [None; 2]);
+    assert_eq!(engine.history.countermove(Some((WhitePawn, e4))), None);
+}
@@ -0,0 +1,61 @@
+use crate::uci::GoCommand;
+
+pub const MAX_DEPTH: u8 = 64;
+
+/// Hard limits on a search, on top of the time budget.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves has been found.
+    pub mate: Option<u32>,
+}
+
+impl SearchLimits {
+    #[must_use]
+    pub fn max_depth(self) -> u8 {
+        // a mate in n needs at most 2n - 1 plies to prove
+        let mate_depth = self.mate.map(|mate| (mate * 2).saturating_sub(1).min(MAX_DEPTH as u32) as u8);
+        let depth = match (self.depth, mate_depth) {
+            (Some(depth), Some(mate)) => depth.min(mate),
+            (depth, mate) => depth.or(mate).unwrap_or(MAX_DEPTH),
+        };
+        depth.clamp(1, MAX_DEPTH)
+    }
+    #[must_use]
+    #[inline]
+    pub fn nodes_exceeded(self, nodes: u64) -> bool {
+        self.nodes.is_some_and(|max| nodes >= max)
+    }
+}
+
+impl From<&GoCommand> for SearchLimits {
+    fn from(command: &GoCommand) -> Self {
+        Self {
+            depth: command.depth.map(|depth| depth.min(MAX_DEPTH as u32) as u8),
+            nodes: command.nodes,
+            mate: command.mate,
+        }
+    }
+}
+
+#[test]
+fn test_search_limits() {
+    use crate::prelude::*;
+
+    let mut engine = Engine::new(Board::start_pos());
+    engine.limits = SearchLimits { depth: Some(3), ..SearchLimits::default() };
+    engine.time_available = std::time::Duration::MAX;
+    engine.search();
+    assert_eq!(engine.depth_reached, 3);
+
+    let mut engine = Engine::new(Board::kiwipete());
+    engine.limits = SearchLimits { nodes: Some(5_000), ..SearchLimits::default() };
+    engine.time_available = std::time::Duration::MAX;
+    engine.search();
+    // the budget is checked between moves, so allow a little overshoot
+    assert!(engine.total_nodes < 5_100);
+
+    assert_eq!(SearchLimits { mate: Some(2), ..SearchLimits::default() }.max_depth(), 3);
+    assert_eq!(SearchLimits { depth: Some(0), mate: Some(1), ..SearchLimits::default() }.max_depth(), 1);
+}
@@ -1,13 +1,28 @@
+pub mod bench;
 pub mod evaluation;
+mod history;
+mod limits;
 mod mobility;
 mod move_ordering;
+mod move_picker;
+pub mod options;
 mod phase;
 mod score;
 mod search;
+mod smp;
 pub mod transposition;
 
-use std::time::{Duration, Instant};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use history::History;
+pub use limits::{SearchLimits, MAX_DEPTH};
+pub use options::{EngineOption, Options};
 pub use phase::Phase;
 use transposition::TranspositionTable;
 
@@ -17,38 +32,118 @@ pub struct Engine {
     pub board: Board,
     pub seen_positions: Vec<Zobrist>,
     pub pv: Moves,
+    /// The best lines of the last depth searched with their scores, best first, up to the `MultiPV` option.
+    pub lines: Vec<(i32, Moves)>,
+    /// Root moves already reported in an earlier line of the current depth.
+    pub(crate) excluded_root_moves: Moves,
     pub depth_from_root: u16,
     pub time_started: Instant,
     pub time_available: Duration,
+    pub limits: SearchLimits,
+    pub options: Options,
+    /// Restricts the root to these moves when set.
+    pub searchmoves: Option<Moves>,
     pub depth_reached: u8,
     pub total_nodes: u64,
     pub effective_nodes: u64,
-    pub force_cancelled: bool,
-    pub transposition_table: TranspositionTable,
+    pub signals: Arc<SearchSignals>,
+    /// Searching on the opponent's time: the time budget only starts on `ponderhit`.
+    pub pondering: bool,
+    /// Shared with the helper threads of a multi-threaded search.
+    pub transposition_table: Arc<TranspositionTable>,
+    /// Positions found in the transposition table during the last search.
+    pub tt_hits: u64,
     pub only_pv_nodes: bool,
+    pub(crate) history: History,
+    /// The moves from the root to the current position with the pieces that made them, `None` for
+    /// null moves.
+    pub(crate) played: Vec<Option<(Piece, Move)>>,
+    /// Stops the search printing `info` lines, e.g. while running a test suite.
+    pub quiet: bool,
+    /// Zero for the thread that decides the best move, counting up for its helpers.
+    pub(crate) thread_id: usize,
+    /// Where a helper thread publishes its node count for the main thread's `info` lines.
+    pub(crate) published_nodes: Option<Arc<AtomicU64>>,
     pub magic: &'static Magic,
 }
 
 impl Engine {
     #[must_use]
     pub fn new(board: Board) -> Self {
+        Self::with_options(board, Options::default())
+    }
+    /// An engine with a transposition table of its own, sized by the options.
+    #[must_use]
+    pub fn with_options(board: Board, options: Options) -> Self {
+        let transposition_table = Arc::new(TranspositionTable::new(options.hash_mb));
+        Self::with_table(board, options, transposition_table)
+    }
+    fn with_table(board: Board, options: Options, transposition_table: Arc<TranspositionTable>) -> Self {
         Self {
             board,
             pv: Moves::new(),
+            lines: Vec::new(),
+            excluded_root_moves: Moves::new(),
             depth_from_root: 0,
             seen_positions: vec![],
             time_started: Instant::now(),
             time_available: Duration::from_secs(4),
+            limits: SearchLimits::default(),
+            transposition_table,
+            tt_hits: 0,
+            options,
+            searchmoves: None,
             depth_reached: 0,
             total_nodes: 0,
             effective_nodes: 0,
-            force_cancelled: false,
-            transposition_table: TranspositionTable::default(),
+            signals: Arc::default(),
+            pondering: false,
             only_pv_nodes: false,
+            history: History::default(),
+            played: Vec::new(),
+            quiet: false,
+            thread_id: 0,
+            published_nodes: None,
             magic: Magic::get(),
         }
     }
+    /// Forgets everything learned from the previous game but keeps the options.
+    pub fn new_game(&mut self) {
+        self.board = Board::start_pos();
+        self.seen_positions.clear();
+        self.pv.clear();
+        self.lines.clear();
+        self.transposition_table.clear();
+        self.history.clear();
+    }
+    /// The opponent's reply from the principal variation, if the search got that far.
+    #[must_use]
+    pub fn ponder_move(&self) -> Option<Move> {
+        self.pv.get(1).copied()
+    }
+    /// The opponent played the expected move, so keep the current search going
+    /// but start counting the normal time budget from now.
+    pub fn ponderhit(&mut self) {
+        self.pondering = false;
+        self.signals.pondering.store(false, Ordering::Relaxed);
+        self.time_started = Instant::now();
+    }
     pub(crate) fn is_cancelled(&mut self) -> bool {
-        self.time_started.elapsed() >= self.time_available || self.force_cancelled
+        if let Some(nodes) = &self.published_nodes {
+            nodes.store(self.total_nodes, Ordering::Relaxed);
+        }
+        if self.pondering && !self.signals.pondering.load(Ordering::Relaxed) {
+            self.ponderhit();
+        }
+        self.signals.stop.load(Ordering::Relaxed)
+            || self.limits.nodes_exceeded(self.total_nodes)
+            || (!self.pondering && self.time_started.elapsed() >= self.time_available)
     }
 }
+
+/// Flags shared between a running search and the thread that controls it.
+#[derive(Default, Debug)]
+pub struct SearchSignals {
+    pub stop: AtomicBool,
+    pub pondering: AtomicBool,
+}
@@ -12,49 +12,99 @@ const MVV_LVA: [[u8; 6]; 6] = [
     [0, 0, 0, 0, 0, 0],       // victim K, attacker P, N, B, R, Q, K
 ];
 
+/// Captures that don't lose material come straight after the TT move, best victim first.
+const WINNING_CAPTURE: i32 = 1 << 24;
+/// The two killers and then the countermove follow, counting down from here.
+const REFUTATION: i32 = 1 << 23;
+/// Captures that lose material come after every quiet move.
+const LOSING_CAPTURE: i32 = -(1 << 24);
+
 impl Engine {
-    pub fn order_moves(&mut self, moves: &mut [Move], killer: Option<Move>) {
+    /// Sorts a whole list of moves, best first. Away from the root the search hands out moves
+    /// with a `MovePicker` instead, which only does this work for the moves it gets to.
+    pub fn order_moves(&mut self, moves: &mut [Move], tt_move: Option<Move>) {
         let pawn_attacks = MoveGenerator::<FullGen>::new(&mut self.board).pawn_attack_map();
         let phase = self.phase();
-        moves.sort_by_cached_key(|&mov| -self.move_order(mov, killer, phase, pawn_attacks));
+        let refutations = self.refutations();
+        let earlier = self.earlier_moves();
+        moves.sort_by_cached_key(|&mov| {
+            -self.move_order(mov, tt_move, refutations, &earlier, phase, pawn_attacks)
+        });
+    }
+    /// The two killers of the current ply, then the countermove to the previous move.
+    pub(crate) fn refutations(&self) -> [Option<Move>; 3] {
+        let killers = self.history.killers(self.depth_from_root as usize);
+        let countermove = self.history.countermove(self.earlier_moves()[0]);
+        [killers[0], killers[1], countermove]
     }
-    fn move_order(&mut self, mov: Move, killer: Option<Move>, phase: Phase, pawn_attacks: Bitboard) -> i32 {
-        let mut score = 0;
-        if self.only_pv_nodes {
-            if let Some(&pv) = self.pv.get(self.depth_from_root as usize) {
-                if pv == mov {
-                    return i16::MAX as i32;
-                }
-            }
-        } else if let Some(killer) = killer {
-            if killer == mov {
-                return i16::MAX as i32;
-            }
+    fn move_order(
+        &mut self,
+        mov: Move,
+        tt_move: Option<Move>,
+        refutations: [Option<Move>; 3],
+        earlier: &[Option<(Piece, Move)>],
+        phase: Phase,
+        pawn_attacks: Bitboard,
+    ) -> i32 {
+        if self.only_pv_nodes && self.pv.get(self.depth_from_root as usize) == Some(&mov) {
+            return i32::MAX;
         }
-        let piece = self.board.get_square(mov.from()).unwrap();
-
-        score += (((abs_piece_square_value(mov.to(), piece, phase)
-            - abs_piece_square_value(mov.from(), piece, phase)) as f32
-            * (phase.earlygame().0 * 0.2)) as f32) as i32;
-
-        if let Some(target_piece) = self.board.get_square(mov.to()) {
-            score += MVV_LVA[target_piece.kind() as usize][piece.kind() as usize] as i32 * 4;
-        } else if mov.flags() == MoveFlags::EnPassant {
-            score += MVV_LVA[Pawn as usize][Pawn as usize] as i32 * 4;
+        if tt_move == Some(mov) {
+            return i32::MAX - 1;
+        }
+        if is_noisy(mov) {
+            return self.noisy_order(mov, self.board.see(mov), phase);
+        }
+        if let Some(index) = refutations.iter().position(|&refutation| refutation == Some(mov)) {
+            return REFUTATION - index as i32;
+        }
+        self.quiet_order(mov, earlier, phase, pawn_attacks)
+    }
+    /// Captures and promotions that don't lose material score above every quiet move, best victim
+    /// first, and the ones that do score below them, the worst last. `exchange` is the move's SEE.
+    pub(crate) fn noisy_order(&self, mov: Move, exchange: i32, phase: Phase) -> i32 {
+        if exchange < 0 {
+            return LOSING_CAPTURE + exchange;
+        }
+        let mut score = WINNING_CAPTURE;
+        if mov.flags().is_capture() {
+            let attacker = self.board.get_square_kind(mov.from()).unwrap();
+            // en passant leaves the target square empty
+            let victim = self.board.get_square_kind(mov.to()).unwrap_or(Pawn);
+            score += MVV_LVA[victim as usize][attacker as usize] as i32 * 4;
         }
-
         if let Some(kind) = mov.flags().promotion().map(PieceKind::from) {
             score += abs_piece_value(kind, phase);
-        };
+        }
+        score
+    }
+    pub(crate) fn quiet_order(
+        &self,
+        mov: Move,
+        earlier: &[Option<(Piece, Move)>],
+        phase: Phase,
+        pawn_attacks: Bitboard,
+    ) -> i32 {
+        let piece = self.board.get_square(mov.from()).unwrap();
+        let mut score = self.history.score(piece, mov, earlier) / 32;
+
+        score += ((abs_piece_square_value(mov.to(), piece, phase)
+            - abs_piece_square_value(mov.from(), piece, phase)) as f32
+            * (phase.earlygame().0 * 0.2)) as i32;
 
         if mov.flags() == MoveFlags::KingCastle || mov.flags() == MoveFlags::QueenCastle {
             score += 10;
         }
 
-        if !mov.flags().is_capture() && piece.kind() != Pawn && !pawn_attacks.contains(mov.to()) {
+        if piece.kind() != Pawn && !pawn_attacks.contains(mov.to()) {
             score += 5;
         }
 
         score
     }
 }
+
+/// Captures and promotions, which are ordered by what they win rather than by history.
+pub(crate) fn is_noisy(mov: Move) -> bool {
+    mov.flags().is_capture() || mov.flags().promotion().is_some()
+}
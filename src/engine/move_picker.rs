@@ -0,0 +1,236 @@
+use movegen::{CapturesOnly, FullGen, Legality, QuietsOnly};
+use smallvec::SmallVec;
+
+use super::move_ordering::is_noisy;
+use crate::prelude::*;
+
+/// Moves with the score they're ordered by and, for captures, their static exchange value.
+type Scored = SmallVec<[(Move, i32, i32); 64]>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    PvMove,
+    TtMove,
+    GenerateCaptures,
+    WinningCaptures,
+    Refutations,
+    GenerateQuiets,
+    Quiets,
+    LosingCaptures,
+    /// Moves that were generated and scored up front, e.g. at the root.
+    Listed,
+    Done,
+}
+
+/// Hands out the legal moves of a position best first in stages: the PV and TT moves, winning
+/// captures, killers, the countermove, quiet moves by history and then losing captures.
+///
+/// Each kind of move is only generated and scored once the stages before it have run out, so a
+/// beta cutoff early on skips the rest of the work.
+pub(crate) struct MovePicker {
+    stage: Stage,
+    tt_move: Option<Move>,
+    /// Only hands out captures, for quiescence.
+    captures_only: bool,
+    /// Moves handed out before they were generated, which mustn't be handed out again.
+    tried: SmallVec<[Move; 5]>,
+    /// The checks and pins of the position, worked out for the first move handed out early.
+    legality: Option<Legality>,
+    refutations: SmallVec<[Move; 3]>,
+    /// The moves of the current stage with their scores, taken best first.
+    scored: Scored,
+    losing_captures: Scored,
+    /// The static exchange value of the last capture handed out.
+    exchange: i32,
+}
+
+impl MovePicker {
+    pub(crate) fn new(tt_move: Option<Move>) -> Self {
+        Self::with_stage(Stage::PvMove, tt_move)
+    }
+    /// A picker for quiescence: captures only, with the TT move first if it is one of them.
+    pub(crate) fn captures(tt_move: Option<Move>) -> Self {
+        Self { captures_only: true, ..Self::with_stage(Stage::GenerateCaptures, tt_move) }
+    }
+    /// Hands out moves that are already in order.
+    pub(crate) fn listed(moves: &[Move]) -> Self {
+        let mut picker = Self::with_stage(Stage::Listed, None);
+        picker.scored = moves.iter().rev().enumerate().map(|(index, &mov)| (mov, index as i32, 0)).collect();
+        picker
+    }
+    fn with_stage(stage: Stage, tt_move: Option<Move>) -> Self {
+        Self {
+            stage,
+            tt_move,
+            captures_only: false,
+            tried: SmallVec::new(),
+            legality: None,
+            refutations: SmallVec::new(),
+            scored: Scored::new(),
+            losing_captures: Scored::new(),
+            exchange: 0,
+        }
+    }
+    pub(crate) fn next(&mut self, engine: &mut Engine) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::PvMove => {
+                    self.stage = Stage::TtMove;
+                    let pv_move = engine.pv.get(engine.depth_from_root as usize).copied();
+                    if let Some(mov) = pv_move.filter(|_| engine.only_pv_nodes) {
+                        if self.try_early(engine, mov) {
+                            return Some(mov);
+                        }
+                    }
+                }
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(mov) = self.tt_move {
+                        if self.try_early(engine, mov) {
+                            return Some(mov);
+                        }
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.stage = Stage::WinningCaptures;
+                    let phase = engine.phase();
+                    for mov in MoveGenerator::<CapturesOnly>::new(&mut engine.board).gen_legal_moves() {
+                        if self.tried.contains(&mov) {
+                            continue;
+                        }
+                        let exchange = engine.board.see(mov);
+                        let score = if self.captures_only && self.tt_move == Some(mov) {
+                            i32::MAX
+                        } else {
+                            engine.noisy_order(mov, exchange, phase)
+                        };
+                        if score < 0 {
+                            self.losing_captures.push((mov, score, exchange));
+                        } else {
+                            self.scored.push((mov, score, exchange));
+                        }
+                    }
+                }
+                Stage::WinningCaptures => {
+                    if let Some((mov, exchange)) = take_best(&mut self.scored) {
+                        self.exchange = exchange;
+                        return Some(mov);
+                    }
+                    if self.captures_only {
+                        self.stage = Stage::LosingCaptures;
+                    } else {
+                        self.stage = Stage::Refutations;
+                        // handed out in reverse
+                        self.refutations = engine.refutations().into_iter().rev().flatten().collect();
+                    }
+                }
+                Stage::Refutations => {
+                    let Some(mov) = self.refutations.pop() else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    // killers and countermoves come from other positions, where they were quiet
+                    if !is_noisy(mov) && self.try_early(engine, mov) {
+                        return Some(mov);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.stage = Stage::Quiets;
+                    let phase = engine.phase();
+                    let earlier = engine.earlier_moves();
+                    let pawn_attacks = MoveGenerator::<FullGen>::new(&mut engine.board).pawn_attack_map();
+                    for mov in MoveGenerator::<QuietsOnly>::new(&mut engine.board).gen_legal_moves() {
+                        if self.tried.contains(&mov) {
+                            continue;
+                        }
+                        // promotions that don't capture are generated with the quiet moves
+                        let score = if is_noisy(mov) {
+                            engine.noisy_order(mov, engine.board.see(mov), phase)
+                        } else {
+                            engine.quiet_order(mov, &earlier, phase, pawn_attacks)
+                        };
+                        self.scored.push((mov, score, 0));
+                    }
+                }
+                Stage::Quiets => {
+                    if let Some((mov, _)) = take_best(&mut self.scored) {
+                        return Some(mov);
+                    }
+                    self.stage = Stage::LosingCaptures;
+                }
+                Stage::LosingCaptures => {
+                    if let Some((mov, exchange)) = take_best(&mut self.losing_captures) {
+                        self.exchange = exchange;
+                        return Some(mov);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Listed => {
+                    if let Some((mov, _)) = take_best(&mut self.scored) {
+                        return Some(mov);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+    /// Like `next` for a captures-only picker, with the static exchange value of each capture,
+    /// which quiescence prunes by.
+    pub(crate) fn next_capture(&mut self, engine: &mut Engine) -> Option<(Move, i32)> {
+        let mov = self.next(engine)?;
+        Some((mov, self.exchange))
+    }
+    /// Checks a move from before the generation stages hasn't been handed out yet and is legal
+    /// here, since it may come from another position.
+    fn try_early(&mut self, engine: &mut Engine, mov: Move) -> bool {
+        if self.tried.contains(&mov) || !engine.board.is_pseudolegal(mov) {
+            return false;
+        }
+        let mut movegen = MoveGenerator::<FullGen>::new(&mut engine.board);
+        let legality = *self.legality.get_or_insert_with(|| movegen.legality());
+        if !movegen.is_legal(mov, legality) {
+            return false;
+        }
+        self.tried.push(mov);
+        true
+    }
+}
+
+/// Removes the move with the highest score, returning it with its exchange value. Searching
+/// instead of sorting saves work when only the first few moves are needed.
+fn take_best(scored: &mut Scored) -> Option<(Move, i32)> {
+    let best = (0..scored.len()).max_by_key(|&index| scored[index].1)?;
+    let (mov, _, exchange) = scored.swap_remove(best);
+    Some((mov, exchange))
+}
+
+#[test]
+fn test_move_picker() {
+    for mut board in [Board::kiwipete(), Board::perft_position_4(), Board::perft_position_5()] {
+        // the search only ever promotes to a queen or a knight
+        let legal = MoveGenerator::<FullGen>::new(&mut board).gen_legal_moves();
+        let mut engine = Engine::new(board.clone());
+        // a TT move from another position, as after a hash collision, is never handed out
+        let empty = (0..64).map(|index| Square::try_from(index).unwrap()).find(|&sq| !board.is_piece_at(sq));
+        let illegal = Move::new(empty.unwrap(), Square::A1, MoveFlags::Quiet);
+        for tt_move in [legal.last().copied(), Some(illegal)] {
+            let mut picker = MovePicker::new(tt_move);
+            let mut order = Vec::new();
+            while let Some(mov) = picker.next(&mut engine) {
+                order.push(mov);
+            }
+            assert_eq!(order.len(), legal.len());
+            assert!(legal.iter().all(|mov| order.contains(mov)));
+            if tt_move != Some(illegal) {
+                assert_eq!(order.first(), tt_move.as_ref());
+            }
+
+            // losing captures come after every quiet move, though the TT move may be one of them
+            let losing = |mov: &Move| is_noisy(*mov) && board.see(*mov) < 0;
+            let rest = &order[1..];
+            let first_losing = rest.iter().position(losing).unwrap_or(rest.len());
+            assert!(rest[first_losing..].iter().all(losing), "{order:?}");
+        }
+    }
+}
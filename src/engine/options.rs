@@ -0,0 +1,275 @@
+use std::{sync::Arc, time::Duration};
+
+use super::transposition::TranspositionTable;
+use crate::{prelude::*, uci::OptionType};
+
+/// Engine settings that can be changed over UCI with `setoption`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub hash_mb: usize,
+    pub threads: usize,
+    pub multipv: usize,
+    pub move_overhead: Duration,
+    pub ponder: bool,
+    pub chess960: bool,
+    pub eval: EvalWeights,
+    pub search: SearchParams,
+}
+
+/// Evaluation terms that are worth tuning without a recompile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalWeights {
+    pub bishop_pair: i32,
+    pub doubled_pawn: i32,
+    pub outpost: i32,
+    pub rook_open_file: i32,
+    /// Percentage applied to the mobility score.
+    pub mobility: i32,
+}
+
+/// Thresholds for reducing and pruning quiet moves late in the move order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchParams {
+    /// Hundredths of a ply every late move reduction starts with.
+    pub lmr_base: i32,
+    /// The reduction grows with `ln(depth) * ln(move number)` divided by this many hundredths.
+    pub lmr_divisor: i32,
+    /// The depth up to which quiet moves past `3 + depth²` are skipped. Zero turns it off.
+    pub lmp_depth: u8,
+    /// The depth up to which quiet moves are skipped when the static evaluation is far below alpha.
+    pub futility_depth: u8,
+    /// How far below alpha, per ply of depth, the static evaluation has to be.
+    pub futility_margin: i32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            hash_mb: 16,
+            threads: 1,
+            multipv: 1,
+            move_overhead: Duration::from_millis(10),
+            ponder: false,
+            chess960: false,
+            eval: EvalWeights::default(),
+            search: SearchParams::default(),
+        }
+    }
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self { bishop_pair: 50, doubled_pawn: 25, outpost: 20, rook_open_file: 20, mobility: 100 }
+    }
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self { lmr_base: 75, lmr_divisor: 225, lmp_depth: 3, futility_depth: 2, futility_margin: 120 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineOption {
+    Hash,
+    ClearHash,
+    Threads,
+    MultiPv,
+    MoveOverhead,
+    Ponder,
+    Chess960,
+    BishopPair,
+    DoubledPawn,
+    Outpost,
+    RookOpenFile,
+    Mobility,
+    LmrBase,
+    LmrDivisor,
+    LmpDepth,
+    FutilityDepth,
+    FutilityMargin,
+}
+
+impl EngineOption {
+    pub const ALL: [Self; 17] = [
+        Self::Hash,
+        Self::ClearHash,
+        Self::Threads,
+        Self::MultiPv,
+        Self::MoveOverhead,
+        Self::Ponder,
+        Self::Chess960,
+        Self::BishopPair,
+        Self::DoubledPawn,
+        Self::Outpost,
+        Self::RookOpenFile,
+        Self::Mobility,
+        Self::LmrBase,
+        Self::LmrDivisor,
+        Self::LmpDepth,
+        Self::FutilityDepth,
+        Self::FutilityMargin,
+    ];
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hash => "Hash",
+            Self::ClearHash => "Clear Hash",
+            Self::Threads => "Threads",
+            Self::MultiPv => "MultiPV",
+            Self::MoveOverhead => "Move Overhead",
+            Self::Ponder => "Ponder",
+            Self::Chess960 => "UCI_Chess960",
+            Self::BishopPair => "BishopPair",
+            Self::DoubledPawn => "DoubledPawn",
+            Self::Outpost => "Outpost",
+            Self::RookOpenFile => "RookOpenFile",
+            Self::Mobility => "Mobility",
+            Self::LmrBase => "LmrBase",
+            Self::LmrDivisor => "LmrDivisor",
+            Self::LmpDepth => "LmpDepth",
+            Self::FutilityDepth => "FutilityDepth",
+            Self::FutilityMargin => "FutilityMargin",
+        }
+    }
+    /// Option names are matched case-insensitively, as the UCI protocol asks.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|option| option.name().eq_ignore_ascii_case(name.trim()))
+    }
+    /// The `(min, max)` range of spin options.
+    #[must_use]
+    pub fn range(self) -> Option<(i64, i64)> {
+        Some(match self {
+            Self::Hash => (1, 1 << 16),
+            Self::Threads => (1, 1024),
+            Self::MultiPv => (1, 256),
+            Self::MoveOverhead => (0, 5000),
+            Self::BishopPair | Self::DoubledPawn | Self::Outpost | Self::RookOpenFile => (-500, 500),
+            Self::Mobility | Self::FutilityMargin => (0, 1000),
+            Self::LmrBase => (0, 300),
+            Self::LmrDivisor => (100, 1000),
+            Self::LmpDepth | Self::FutilityDepth => (0, 10),
+            Self::ClearHash | Self::Ponder | Self::Chess960 => return None,
+        })
+    }
+    #[must_use]
+    pub fn option_type(self) -> OptionType {
+        let defaults = Options::default();
+        match self {
+            Self::ClearHash => OptionType::Button,
+            Self::Ponder => OptionType::Check { default: Some(defaults.ponder) },
+            Self::Chess960 => OptionType::Check { default: Some(defaults.chess960) },
+            _ => {
+                let (min, max) = self.range().expect("Other options should be spin options");
+                let default = Some(self.spin_value(&defaults));
+                OptionType::Spin { default, min: Some(min), max: Some(max) }
+            }
+        }
+    }
+    fn spin_value(self, options: &Options) -> i64 {
+        match self {
+            Self::Hash => options.hash_mb as i64,
+            Self::Threads => options.threads as i64,
+            Self::MultiPv => options.multipv as i64,
+            Self::MoveOverhead => options.move_overhead.as_millis() as i64,
+            Self::BishopPair => options.eval.bishop_pair.into(),
+            Self::DoubledPawn => options.eval.doubled_pawn.into(),
+            Self::Outpost => options.eval.outpost.into(),
+            Self::RookOpenFile => options.eval.rook_open_file.into(),
+            Self::Mobility => options.eval.mobility.into(),
+            Self::LmrBase => options.search.lmr_base.into(),
+            Self::LmrDivisor => options.search.lmr_divisor.into(),
+            Self::LmpDepth => options.search.lmp_depth.into(),
+            Self::FutilityDepth => options.search.futility_depth.into(),
+            Self::FutilityMargin => options.search.futility_margin.into(),
+            Self::ClearHash | Self::Ponder | Self::Chess960 => unreachable!("{self:?} is not a spin option"),
+        }
+    }
+}
+
+impl Engine {
+    /// Applies a UCI `setoption` command.
+    ///
+    /// # Errors
+    /// Returns a message for the GUI when the option is unknown or the value is invalid.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), String> {
+        let option = EngineOption::from_name(name).ok_or_else(|| format!("Unknown option '{name}'"))?;
+        let value = value.map(str::trim);
+
+        match option {
+            EngineOption::ClearHash => self.transposition_table.clear(),
+            EngineOption::Ponder | EngineOption::Chess960 => {
+                let check = match value {
+                    Some(value) if value.eq_ignore_ascii_case("true") => true,
+                    Some(value) if value.eq_ignore_ascii_case("false") => false,
+                    _ => return Err(format!("{} expects true or false", option.name())),
+                };
+                self.set_check(option, check);
+            }
+            _ => {
+                let (min, max) = option.range().expect("Other options should be spin options");
+                let spin = value
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .filter(|spin| (min..=max).contains(spin))
+                    .ok_or_else(|| format!("{} expects a number from {min} to {max}", option.name()))?;
+                self.set_spin(option, spin);
+            }
+        }
+        Ok(())
+    }
+    fn set_check(&mut self, option: EngineOption, check: bool) {
+        match option {
+            EngineOption::Ponder => self.options.ponder = check,
+            EngineOption::Chess960 => {
+                self.options.chess960 = check;
+                self.board.chess960 = check;
+            }
+            _ => unreachable!("{option:?} is not a check option"),
+        }
+    }
+    fn set_spin(&mut self, option: EngineOption, spin: i64) {
+        match option {
+            EngineOption::Hash => {
+                self.options.hash_mb = spin as usize;
+                self.transposition_table = Arc::new(TranspositionTable::new(self.options.hash_mb));
+            }
+            EngineOption::Threads => self.options.threads = spin as usize,
+            EngineOption::MultiPv => self.options.multipv = spin as usize,
+            EngineOption::MoveOverhead => self.options.move_overhead = Duration::from_millis(spin as u64),
+            EngineOption::BishopPair => self.options.eval.bishop_pair = spin as i32,
+            EngineOption::DoubledPawn => self.options.eval.doubled_pawn = spin as i32,
+            EngineOption::Outpost => self.options.eval.outpost = spin as i32,
+            EngineOption::RookOpenFile => self.options.eval.rook_open_file = spin as i32,
+            EngineOption::Mobility => self.options.eval.mobility = spin as i32,
+            EngineOption::LmrBase => self.options.search.lmr_base = spin as i32,
+            EngineOption::LmrDivisor => self.options.search.lmr_divisor = spin as i32,
+            EngineOption::LmpDepth => self.options.search.lmp_depth = spin as u8,
+            EngineOption::FutilityDepth => self.options.search.futility_depth = spin as u8,
+            EngineOption::FutilityMargin => self.options.search.futility_margin = spin as i32,
+            EngineOption::ClearHash | EngineOption::Ponder | EngineOption::Chess960 => {
+                unreachable!("{option:?} is not a spin option")
+            }
+        }
+    }
+}
+
+#[test]
+fn test_set_option() {
+    let mut engine = Engine::new(Board::start_pos());
+    assert_eq!(engine.set_option("multipv", Some("3")), Ok(()));
+    assert_eq!(engine.options.multipv, 3);
+    assert_eq!(engine.set_option("Move Overhead", Some("50")), Ok(()));
+    assert_eq!(engine.options.move_overhead, Duration::from_millis(50));
+    assert_eq!(engine.set_option("ponder", Some("true")), Ok(()));
+    assert!(engine.options.ponder);
+    assert_eq!(engine.set_option("UCI_Chess960", Some("true")), Ok(()));
+    assert!(engine.board.chess960);
+    assert_eq!(engine.set_option("clear hash", None), Ok(()));
+
+    assert!(engine.set_option("MultiPV", Some("0")).is_err());
+    assert!(engine.set_option("Hash", Some("lots")).is_err());
+    assert!(engine.set_option("Ponder", None).is_err());
+    assert!(engine.set_option("Contempt", Some("10")).is_err());
+    assert_eq!(engine.options.multipv, 3);
+}
@@ -8,11 +8,57 @@ use std::{
 pub struct Eval(pub i32);
 
 impl Eval {
-    pub const INFINITY: Self = Self(i32::MAX - 1);
-    pub const MATE: Self = Self(i32::MAX);
+    pub const INFINITY: Self = Self(100_001);
+    /// Being checkmated at the root. Mates further from the root score closer to zero.
+    pub const MATE: Self = Self(100_000);
+    /// The longest mate, in plies, that scores can represent.
+    pub const MAX_MATE_PLY: i32 = 1000;
 
     const NEG_INF: Self = Self(-Self::INFINITY.0);
     const NEG_MATE: Self = Self(-Self::MATE.0);
+
+    /// The score for the side to move being checkmated `ply` plies from the root.
+    #[must_use]
+    #[inline]
+    pub const fn mated_in(ply: u16) -> Self {
+        Self(-Self::MATE.0 + ply as i32)
+    }
+    #[must_use]
+    #[inline]
+    pub const fn is_mate(self) -> bool {
+        self.0.abs() >= Self::MATE.0 - Self::MAX_MATE_PLY
+    }
+    /// Full moves until mate, negative if the side to move is getting mated.
+    #[must_use]
+    pub const fn mate_in(self) -> Option<i32> {
+        if !self.is_mate() {
+            None
+        } else if self.0 > 0 {
+            Some((Self::MATE.0 - self.0 + 1) / 2)
+        } else {
+            Some(-(Self::MATE.0 + self.0) / 2)
+        }
+    }
+    /// Makes mate scores relative to the node at `ply` instead of the root, for storing.
+    #[must_use]
+    #[inline]
+    pub const fn relative_to_node(self, ply: u16) -> Self {
+        match self.0 {
+            _ if !self.is_mate() => self,
+            score if score > 0 => Self(score + ply as i32),
+            score => Self(score - ply as i32),
+        }
+    }
+    /// Makes a stored mate score relative to the root again, the inverse of [`Eval::relative_to_node`].
+    #[must_use]
+    #[inline]
+    pub const fn relative_to_root(self, ply: u16) -> Self {
+        match self.0 {
+            _ if !self.is_mate() => self,
+            score if score > 0 => Self(score - ply as i32),
+            score => Self(score + ply as i32),
+        }
+    }
 }
 
 impl Mul<f32> for Eval {
@@ -42,3 +88,18 @@ impl fmt::Debug for Eval {
         .finish()
     }
 }
+
+#[test]
+fn test_mate_scores() {
+    assert_eq!(Eval::mated_in(0).mate_in(), Some(0));
+    assert_eq!((-Eval::mated_in(1)).mate_in(), Some(1));
+    assert_eq!(Eval::mated_in(2).mate_in(), Some(-1));
+    assert_eq!((-Eval::mated_in(5)).mate_in(), Some(3));
+    assert_eq!(Eval(250).mate_in(), None);
+
+    let score = -Eval::mated_in(7);
+    assert_eq!(score.relative_to_node(4).relative_to_root(4), score);
+    // the same mate seen from a node two plies closer to it
+    assert_eq!(score.relative_to_node(4).relative_to_root(2), -Eval::mated_in(5));
+    assert_eq!(Eval(-40).relative_to_node(9), Eval(-40));
+}
@@ -1,90 +1,174 @@
-use std::time::Instant;
+use std::{sync::atomic::Ordering, time::Instant};
 
 use movegen::FullGen;
 
-use super::{transposition::Nodetype, Engine};
+use super::{move_ordering::is_noisy, move_picker::MovePicker, smp::Helper, transposition::Nodetype, Engine};
 use crate::{
     engine::score::Eval,
     prelude::*,
-    uci::{Info, Score, UciResponse},
+    uci::{Bound, Info, Score, UciResponse},
 };
 
+/// Half the width of the first aspiration window, in centipawns.
+const ASPIRATION_WINDOW: i32 = 25;
+/// How much a capture in quiescence may gain beyond its exchange value, e.g. through position.
+const DELTA_MARGIN: i32 = 200;
+
 impl Engine {
     pub fn search(&mut self) -> Move {
         self.time_started = Instant::now();
         self.total_nodes = 0;
         self.effective_nodes = 0;
-        self.force_cancelled = false;
-        self.transposition_table.num_hits = 0;
+        self.pondering = self.signals.pondering.load(Ordering::Relaxed);
+        self.tt_hits = 0;
+        self.pv.clear();
+        self.lines.clear();
+        if self.thread_id == 0 {
+            self.transposition_table.new_search();
+        }
+        let helpers = if self.thread_id == 0 { self.start_helpers() } else { Vec::new() };
 
-        let mut best_move = self.board.gen_legal_moves().first().copied().unwrap_or(Move::NULL);
+        let root_moves = match &self.searchmoves {
+            Some(moves) if !moves.is_empty() => moves.clone(),
+            _ => self.board.gen_legal_moves(),
+        };
+        let mut best_move = root_moves.first().copied().unwrap_or(Move::NULL);
+        let num_lines = self.options.multipv.clamp(1, root_moves.len().max(1));
 
-        for depth in 1..=64 {
-            if self.time_started.elapsed() > self.time_available / 2 {
+        let max_depth = self.limits.max_depth();
+        for depth in 1..=max_depth {
+            // every other helper searches a ply deeper, spreading the threads over more depths
+            let depth = if self.thread_id % 2 == 1 { (depth + 1).min(max_depth) } else { depth };
+            if self.is_cancelled() || (!self.pondering && self.time_started.elapsed() > self.time_available / 2)
+            {
                 break;
             }
-            self.only_pv_nodes = true;
-            let mut new_pv = Moves::new();
-            let score = self.negamax(-Eval::INFINITY.0, Eval::INFINITY.0, depth, &mut new_pv, None).0;
-            if self.is_cancelled() {
-                break;
+            // each line searches the root with the moves of the lines before it left out
+            let mut lines = Vec::new();
+            self.excluded_root_moves.clear();
+            while lines.len() < num_lines {
+                let multipv = (num_lines > 1).then_some(lines.len() as u32 + 1);
+                let previous = self.lines.get(lines.len()).map(|&(score, _)| score);
+                let (score, pv) = self.aspiration_search(depth, previous, multipv, &helpers);
+                if self.is_cancelled() {
+                    break;
+                }
+                let Some(&first) = pv.first() else {
+                    lines.push((score, pv));
+                    break;
+                };
+                self.excluded_root_moves.push(first);
+                lines.push((score, pv));
             }
-            self.pv = new_pv.into_iter().rev().collect();
+            self.excluded_root_moves.clear();
+            let Some((score, pv)) = lines.first() else { break };
+            let score = *score;
+            self.pv = pv.clone();
             best_move = *self.pv.first().unwrap_or(&best_move);
             self.effective_nodes = self.total_nodes;
             self.depth_reached = depth;
+            let completed = lines.len() == num_lines;
+            self.lines = lines;
 
-            let is_checkmate = score.abs() >= Eval::INFINITY.0;
-
-            let score = if is_checkmate {
-                Score::Mate { mate: depth as i32 / 2 * score.signum() }
-            } else {
-                Score::Centipawns { cp: score, bounds: None }
-            };
-
-            let time_taken = self.time_started.elapsed();
-            let info = Info {
-                depth: Some(depth as u32),
-                score: Some(score),
-                nodes: Some(self.total_nodes),
-                time: Some(time_taken),
-                nps: Some((self.total_nodes as f64 / time_taken.as_secs_f64()) as u32),
-                pv: Some(self.pv.clone()),
-                ..Info::default()
-            };
-            #[cfg(feature = "tracing")]
-            tracing::info!("{info}");
-            println!("{}", UciResponse::Info(Box::new(info)));
+            for (index, (score, pv)) in self.lines.iter().enumerate() {
+                let score = match Eval(*score).mate_in() {
+                    Some(mate) => Score::Mate { mate },
+                    None => Score::Centipawns { cp: *score, bounds: None },
+                };
+                let multipv = (num_lines > 1).then_some(index as u32 + 1);
+                self.send_info(depth, multipv, score, pv, &helpers);
+            }
 
-            if is_checkmate {
+            if !completed || Eval(score).mate_in().is_some() {
                 break;
             }
         }
+        for helper in helpers {
+            helper.stop();
+        }
         best_move
     }
+    /// Searches the root in a narrow window around the score from the previous depth, widening
+    /// the side that failed until the score falls inside it. Each fail is reported as a bound.
+    fn aspiration_search(
+        &mut self,
+        depth: u8,
+        previous: Option<i32>,
+        multipv: Option<u32>,
+        helpers: &[Helper],
+    ) -> (i32, Moves) {
+        let mut delta = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match previous {
+            Some(score) if !Eval(score).is_mate() => (score - delta, score + delta),
+            _ => (-Eval::INFINITY.0, Eval::INFINITY.0),
+        };
+        loop {
+            self.only_pv_nodes = true;
+            let mut line = Moves::new();
+            let score = self.negamax(alpha, beta, depth, &mut line);
+            let pv: Moves = line.into_iter().rev().collect();
+            if self.is_cancelled() {
+                return (score, pv);
+            }
+            if score <= alpha && alpha > -Eval::INFINITY.0 {
+                let bound = Score::Centipawns { cp: alpha, bounds: Some(Bound::Upper) };
+                self.send_info(depth, multipv, bound, &pv, helpers);
+                alpha = (alpha - delta).max(-Eval::INFINITY.0);
+            } else if score >= beta && beta < Eval::INFINITY.0 {
+                let bound = Score::Centipawns { cp: beta, bounds: Some(Bound::Lower) };
+                self.send_info(depth, multipv, bound, &pv, helpers);
+                beta = (beta + delta).min(Eval::INFINITY.0);
+            } else {
+                return (score, pv);
+            }
+            delta *= 2;
+        }
+    }
+    fn send_info(&self, depth: u8, multipv: Option<u32>, score: Score, pv: &[Move], helpers: &[Helper]) {
+        let time_taken = self.time_started.elapsed();
+        let nodes = self.total_nodes + helpers.iter().map(Helper::nodes).sum::<u64>();
+        let info = Info {
+            depth: Some(depth as u32),
+            multipv,
+            score: Some(score),
+            nodes: Some(nodes),
+            time: Some(time_taken),
+            nps: Some((nodes as f64 / time_taken.as_secs_f64()) as u32),
+            pv: (!pv.is_empty()).then(|| pv.iter().map(|&mov| self.board.uci_move(mov)).collect()),
+            hash_full: Some(self.transposition_table.hashfull()),
+            ..Info::default()
+        };
+        #[cfg(feature = "tracing")]
+        tracing::info!("{info}");
+        if !self.quiet {
+            println!("{}", UciResponse::Info(Box::new(info)));
+        }
+    }
     fn seen_position(&self) -> bool {
         self.seen_positions.iter().filter(|&&sq| sq == self.board.zobrist).count() > 1
     }
     #[allow(clippy::too_many_lines)]
-    pub(crate) fn negamax(
-        &mut self,
-        mut alpha: i32,
-        beta: i32,
-        depth: u8,
-        pline: &mut Moves,
-        killer_move: Option<Move>,
-    ) -> (i32, Option<Move>) {
+    pub(crate) fn negamax(&mut self, mut alpha: i32, beta: i32, depth: u8, pline: &mut Moves) -> i32 {
         if self.depth_from_root != 0 && self.seen_position() {
-            return (0, None);
+            return 0;
+        }
+        if self.depth_from_root != 0 && self.board.fifty_move_draw() {
+            let checkmated = self.board.in_check() && self.board.gen_legal_moves().is_empty();
+            if !checkmated {
+                return 0;
+            }
         }
         if self.depth_from_root > 0 {
-            if let Some(eval) = self.transposition_table.get(&self.board, alpha, beta, depth) {
-                return (eval, None);
+            if let Some(eval) =
+                self.transposition_table.get(&self.board, alpha, beta, depth, self.depth_from_root)
+            {
+                self.tt_hits += 1;
+                return eval;
             }
         }
         if depth == 0 {
             self.only_pv_nodes = false;
-            return (self.negamax_search_all_captures(alpha, beta), None);
+            return self.negamax_search_all_captures(alpha, beta);
         }
         if self.depth_from_root > 0 {
             self.total_nodes += 1;
@@ -103,7 +187,9 @@ impl Engine {
             }
             let unmake = self.board.make_null_move();
             self.depth_from_root += 1;
-            let score = -self.negamax(-beta, -alpha, depth - 3, &mut Moves::new(), None).0;
+            self.played.push(None);
+            let score = -self.negamax(-beta, -alpha, depth - 3, &mut Moves::new());
+            self.played.pop();
             self.depth_from_root -= 1;
             self.board.unmake_null_move(unmake);
             if score >= beta {
@@ -111,80 +197,149 @@ impl Engine {
                     &self.board,
                     &self.seen_positions,
                     depth - 2,
+                    self.depth_from_root,
                     beta,
                     Nodetype::Beta,
-                    0,
+                    None,
                 );
-                return (beta, None);
+                return beta;
             }
         }
 
-        let mut moves = MoveGenerator::<FullGen>::new(&mut self.board).gen_pseudolegal_moves();
-        let mut encountered_legal_move = false;
-
-        self.order_moves(&mut moves, killer_move);
+        let tt_move = self.transposition_table.get_move(&self.board);
+        let mut picker = if self.depth_from_root == 0 {
+            let mut moves = match &self.searchmoves {
+                Some(searchmoves) if !searchmoves.is_empty() => searchmoves.clone(),
+                _ => MoveGenerator::<FullGen>::new(&mut self.board).gen_legal_moves(),
+            };
+            moves.retain(|mov| !self.excluded_root_moves.contains(mov));
+            self.order_moves(&mut moves, tt_move);
+            MovePicker::listed(&moves)
+        } else {
+            MovePicker::new(tt_move)
+        };
         let mut nodetype = Nodetype::Alpha;
+        let mut best_move = None;
 
-        let curr_nodes = self.total_nodes;
-        let mut killer_move = None;
-        for mov in moves {
-            if !MoveGenerator::<FullGen>::new(&mut self.board).is_legal(mov) {
+        let params = self.options.search;
+        let in_check = self.board.in_check();
+        // the root and positions in check search every move at full depth
+        let can_prune = self.depth_from_root > 0 && !in_check;
+        // quiet moves can't bring a position this far below alpha back up
+        let futile = can_prune
+            && depth <= params.futility_depth
+            && self.evaluate() + params.futility_margin * i32::from(depth) <= alpha;
+
+        let mut quiets_tried = Moves::new();
+        let mut moves_picked = 0;
+        while let Some(mov) = picker.next(self) {
+            let index = moves_picked;
+            moves_picked += 1;
+            let quiet = !is_noisy(mov);
+            let late = depth <= params.lmp_depth && index >= 3 + usize::from(depth) * usize::from(depth);
+            if can_prune && index > 0 && quiet && (futile || late) && !self.board.gives_check(mov) {
                 continue;
             }
+
+            let piece = self.board.get_square(mov.from()).unwrap();
             let mut line = Moves::new();
-            encountered_legal_move = true;
             let unmake = self.board.make_move(mov);
+            let gives_check = self.board.in_check();
             self.seen_positions.push(self.board.zobrist);
+            self.played.push(Some((piece, mov)));
             self.depth_from_root += 1;
 
-            let extension = self.board.in_check() as u8;
-
-            let (score, chosen_move) =
-                self.negamax(-beta, -alpha, depth - 1 + extension, &mut line, killer_move);
+            let extension = gives_check as u8;
+            let child_depth = depth - 1 + extension;
+            let reduction = if can_prune && depth >= 3 && index >= 3 && quiet && !gives_check {
+                self.late_move_reduction(depth, index).min(child_depth - 1)
+            } else {
+                0
+            };
+            // later moves only have to be proven worse than the best so far, which a zero window
+            // does cheaply, at first with a reduced depth; one that turns out better is searched
+            // again at full depth, then with the full window
+            let mut score = if index == 0 {
+                self.negamax(-beta, -alpha, child_depth, &mut line)
+            } else {
+                self.negamax(-alpha - 1, -alpha, child_depth - reduction, &mut line)
+            };
+            if reduction > 0 && -score > alpha {
+                line.clear();
+                score = self.negamax(-alpha - 1, -alpha, child_depth, &mut line);
+            }
+            if index > 0 && -score > alpha && -score < beta {
+                line.clear();
+                score = self.negamax(-beta, -alpha, child_depth, &mut line);
+            }
             let score = -score;
-            killer_move = chosen_move;
 
             self.depth_from_root -= 1;
+            self.played.pop();
             self.seen_positions.pop();
             self.board.unmake_move(unmake);
             if self.is_cancelled() {
-                return (0, None);
+                return 0;
             }
             if score > alpha {
                 line.push(mov);
                 *pline = line;
                 alpha = score;
                 nodetype = Nodetype::Exact;
+                best_move = Some(mov);
             }
             if score >= beta {
+                if quiet {
+                    self.update_history(mov, &quiets_tried, depth);
+                }
                 self.transposition_table.insert(
                     &self.board,
                     &self.seen_positions,
                     depth,
+                    self.depth_from_root,
                     beta,
                     Nodetype::Beta,
-                    self.total_nodes - curr_nodes,
+                    Some(mov),
                 );
-                return (beta, Some(mov));
+                return beta;
+            }
+            if quiet {
+                quiets_tried.push(mov);
             }
         }
-
-        if !encountered_legal_move {
-            if self.board.in_check() {
-                return (-Eval::MATE.0, None);
+        if moves_picked == 0 {
+            if in_check {
+                return Eval::mated_in(self.depth_from_root).0;
             }
-            return (0, None);
+            return 0;
         }
 
-        self.transposition_table.insert(
-            &self.board,
-            &self.seen_positions,
-            depth,
-            alpha,
-            nodetype,
-            self.total_nodes - curr_nodes,
-        );
-        (alpha, None)
+        // a root restricted by searchmoves or to the lines not yet reported would store a score and
+        // move that don't hold for the whole position
+        let restricted_root = self.depth_from_root == 0
+            && (!self.excluded_root_moves.is_empty()
+                || self.searchmoves.as_ref().is_some_and(|moves| !moves.is_empty()));
+        if !restricted_root {
+            self.transposition_table.insert(
+                &self.board,
+                &self.seen_positions,
+                depth,
+                self.depth_from_root,
+                alpha,
+                nodetype,
+                best_move,
+            );
+        }
+        alpha
+    }
+
+    /// How many plies less than usual to search a late quiet move, growing with both the depth and
+    /// how far down the move order it is.
+    fn late_move_reduction(&self, depth: u8, index: usize) -> u8 {
+        let params = self.options.search;
+        let base = params.lmr_base as f32 / 100.0;
+        let divisor = params.lmr_divisor as f32 / 100.0;
+        (base + f32::from(depth).ln() * (index as f32).ln() / divisor) as u8
     }
 
     fn negamax_search_all_captures(&mut self, mut alpha: i32, beta: i32) -> i32 {
@@ -196,15 +351,15 @@ impl Engine {
         }
         alpha = alpha.max(eval);
 
-        let mut moves = self.board.gen_pseudolegal_capture_moves();
-        self.order_moves(&mut moves, None);
-
+        let tt_move = self.transposition_table.get_move(&self.board);
+        let mut picker = MovePicker::captures(tt_move);
         let mut encountered_legal_move = false;
-        for mov in moves {
-            if !MoveGenerator::<FullGen>::new(&mut self.board).is_legal(mov) {
+        while let Some((mov, exchange)) = picker.next_capture(self) {
+            encountered_legal_move = true;
+            // skip captures that lose material, or can't win enough of it to get back up to alpha
+            if exchange < 0 || eval + exchange + DELTA_MARGIN <= alpha {
                 continue;
             }
-            encountered_legal_move = true;
             let unmake = self.board.make_move(mov);
             self.depth_from_root += 1;
             let score = -self.negamax_search_all_captures(-beta, -alpha);
@@ -221,14 +376,87 @@ impl Engine {
             alpha = alpha.max(score);
         }
 
-        if !encountered_legal_move {
-            let mut movegen = MoveGenerator::<FullGen>::new(&mut self.board);
-            let legal_moves = movegen.gen_pseudolegal_moves().iter().any(|&mov| movegen.is_legal(mov));
-            if !legal_moves {
-                return if self.board.in_check() { -Eval::MATE.0 } else { 0 };
-            }
+        if !encountered_legal_move
+            && MoveGenerator::<FullGen>::new(&mut self.board).gen_legal_moves().is_empty()
+        {
+            return if self.board.in_check() { Eval::mated_in(self.depth_from_root).0 } else { 0 };
         }
 
         alpha
     }
 }
+
+#[test]
+fn test_searchmoves() {
+    let mut engine = Engine::new(Board::start_pos());
+    let searchmoves: Moves = ["a2a3", "h2h4"].into_iter().map(|mov| mov.parse().unwrap()).collect();
+    engine.searchmoves = Some(searchmoves.clone());
+    engine.limits.depth = Some(3);
+    assert!(searchmoves.contains(&engine.search()));
+}
+
+#[test]
+fn test_multipv() {
+    let mut engine = Engine::new(Board::kiwipete());
+    engine.quiet = true;
+    engine.set_option("MultiPV", Some("3")).unwrap();
+    engine.limits.depth = Some(3);
+    let best_move = engine.search();
+
+    assert_eq!(engine.lines.len(), 3);
+    assert_eq!(engine.lines[0].1, engine.pv);
+    assert_eq!(engine.pv[0], best_move);
+    let firsts: Vec<Move> = engine.lines.iter().map(|(_, pv)| pv[0]).collect();
+    assert!(firsts[0] != firsts[1] && firsts[1] != firsts[2] && firsts[0] != firsts[2]);
+    assert!(engine.lines.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+
+    // never more lines than legal moves
+    engine.board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    engine.set_option("MultiPV", Some("10")).unwrap();
+    engine.search();
+    assert_eq!(engine.lines.len(), 3);
+}
+
+#[test]
+fn test_aspiration_windows() {
+    // a window far from the real score has to fail and widen until it finds the same score
+    let mut full = Engine::new(Board::kiwipete());
+    let (score, _) = full.aspiration_search(4, None, None, &[]);
+    for previous in [score - 700, score + 700] {
+        let mut engine = Engine::new(Board::kiwipete());
+        engine.quiet = true;
+        assert_eq!(engine.aspiration_search(4, Some(previous), None, &[]).0, score);
+    }
+}
+
+#[test]
+fn test_late_move_pruning() {
+    // reducing and pruning late moves saves work without missing the mate in WAC.001
+    let fen = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -";
+    let mut nodes = Vec::new();
+    for options in
+        [&[][..], &[("LmrBase", "0"), ("LmrDivisor", "1000"), ("LmpDepth", "0"), ("FutilityDepth", "0")]]
+    {
+        let mut engine = Engine::new(Board::from_fen(fen).unwrap());
+        for (name, value) in options {
+            engine.set_option(name, Some(value)).unwrap();
+        }
+        engine.quiet = true;
+        engine.limits.depth = Some(5);
+        assert_eq!(engine.search().to_string(), "g3g6");
+        nodes.push(engine.total_nodes);
+    }
+    assert!(nodes[0] < nodes[1], "{nodes:?}");
+}
+
+#[test]
+fn test_fifty_move_draw() {
+    // every white move either draws by the fifty-move rule or gives away the queen
+    let mut engine = Engine::new(Board::from_fen("8/8/8/4k3/8/8/8/4K2Q w - - 99 80").unwrap());
+    let score = engine.negamax(-Eval::INFINITY.0, Eval::INFINITY.0, 3, &mut Moves::new());
+    assert_eq!(score, 0);
+
+    let mut engine = Engine::new(Board::from_fen("8/8/8/4k3/8/8/8/4K2Q w - - 90 80").unwrap());
+    let score = engine.negamax(-Eval::INFINITY.0, Eval::INFINITY.0, 3, &mut Moves::new());
+    assert!(score > 500);
+}
@@ -0,0 +1,85 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use super::{Engine, SearchLimits, SearchSignals};
+
+/// A thread searching the same position as the main search thread. Lazy SMP: it only helps by
+/// filling the shared transposition table, and its own best move is thrown away.
+pub(crate) struct Helper {
+    signals: Arc<SearchSignals>,
+    nodes: Arc<AtomicU64>,
+    handle: JoinHandle<()>,
+}
+
+impl Engine {
+    /// Starts a helper for every thread past the first. Helpers ignore the clock and node limit
+    /// and run until the main thread stops them.
+    pub(crate) fn start_helpers(&self) -> Vec<Helper> {
+        (1..self.options.threads)
+            .map(|thread_id| {
+                let mut helper = Engine::with_table(
+                    self.board.clone(),
+                    self.options.clone(),
+                    Arc::clone(&self.transposition_table),
+                );
+                helper.seen_positions.clone_from(&self.seen_positions);
+                helper.searchmoves.clone_from(&self.searchmoves);
+                helper.limits = SearchLimits { nodes: None, ..self.limits };
+                helper.options.multipv = 1;
+                helper.time_available = Duration::MAX;
+                helper.quiet = true;
+                helper.thread_id = thread_id;
+
+                let nodes = Arc::new(AtomicU64::new(0));
+                helper.published_nodes = Some(Arc::clone(&nodes));
+                let signals = Arc::clone(&helper.signals);
+                let handle = thread::spawn(move || {
+                    helper.search();
+                });
+                Helper { signals, nodes, handle }
+            })
+            .collect()
+    }
+}
+
+impl Helper {
+    pub(crate) fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+    /// Stops the search and waits for the thread to finish.
+    pub(crate) fn stop(self) {
+        self.signals.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("Helper thread panicked");
+    }
+}
+
+#[test]
+fn test_lazy_smp() {
+    use crate::prelude::*;
+
+    let mut engine = Engine::new(Board::kiwipete());
+    engine.set_option("Threads", Some("4")).unwrap();
+    engine.quiet = true;
+    engine.limits.depth = Some(5);
+    engine.time_available = Duration::MAX;
+    // left alone, the helpers stop at the depth limit, having filled the shared table by themselves
+    for helper in engine.start_helpers() {
+        helper.handle.join().unwrap();
+    }
+    assert!(engine.transposition_table.used() > 0);
+
+    let best_move = engine.search();
+    assert!(engine.board.gen_legal_moves().contains(&best_move));
+    assert_eq!(engine.depth_reached, 5);
+
+    // the helpers finish with the main thread, so searching again works the same way
+    engine.board = Board::from_fen("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -").unwrap();
+    engine.seen_positions = vec![engine.board.zobrist];
+    assert_eq!(engine.search().to_string(), "g3g6");
+}
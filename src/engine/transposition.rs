@@ -1,94 +1,291 @@
-use std::{
-    collections::HashMap,
-    hash::{BuildHasherDefault, Hasher},
-};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 use super::score::Eval;
 use crate::prelude::*;
 
-#[derive(Default)]
+const BUCKET_SIZE: usize = 4;
+const DEFAULT_SIZE_MB: usize = 16;
+const AGE_BITS: u8 = 6;
+const AGE_MASK: u8 = (1 << AGE_BITS) - 1;
+
+/// A fixed-size hash table of search results, indexed by zobrist key and shared by all search threads.
+///
+/// Entries are grouped into small buckets. A new entry replaces the least valuable entry in its
+/// bucket, preferring entries from earlier searches and then the ones searched to the lowest depth.
+///
+/// Slots are written without locking. Each stores the key xor'd with the packed entry, so a slot torn
+/// by two threads writing at once fails the key check and reads as a miss.
 pub struct TranspositionTable {
-    inner: HashMap<Zobrist, Entry, BuildHasherDefault<NoHasher>>,
-    pub num_hits: u64,
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+#[derive(Default)]
+struct Bucket([Slot; BUCKET_SIZE]);
+
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[repr(u8)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Nodetype {
-    Exact,
-    Alpha,
-    Beta,
+    Exact = 1,
+    Alpha = 2,
+    Beta = 3,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Entry {
+    pub eval: i32,
+    /// The best move found, or the move that caused the cutoff. `Move::NULL` if unknown.
+    pub mov: Move,
+    pub depth: u8,
+    /// The nodetype in the low two bits, the generation it was written in above that.
+    /// Zero marks an empty slot.
+    flags: u8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE_MB)
+    }
 }
 
 impl TranspositionTable {
+    /// Creates a table using at most `mb` megabytes, rounded down to a power of two number of buckets.
+    #[must_use]
+    pub fn new(mb: usize) -> Self {
+        let num_buckets = (mb * 1024 * 1024 / size_of::<Bucket>()).max(1);
+        let num_buckets = 1 << num_buckets.ilog2();
+        Self { buckets: (0..num_buckets).map(|_| Bucket::default()).collect(), generation: AtomicU8::new(0) }
+    }
+    /// The number of entries the table can hold.
     #[must_use]
     pub fn capacity(&self) -> usize {
-        self.inner.capacity()
+        self.buckets.len() * BUCKET_SIZE
+    }
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flat_map(|bucket| &bucket.0) {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+    /// Marks the start of a new search so entries from older searches are replaced first.
+    pub fn new_search(&self) {
+        let generation = self.generation();
+        self.generation.store((generation + 1) & AGE_MASK, Ordering::Relaxed);
+    }
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+    /// How full the table is in per-mille, estimated from the entries written by the current search.
+    #[must_use]
+    pub fn hashfull(&self) -> u32 {
+        let sample = &self.buckets[..self.buckets.len().min(1000 / BUCKET_SIZE)];
+        (self.count_current(sample) * 1000 / (sample.len() * BUCKET_SIZE)) as u32
+    }
+    /// The number of entries written by the current search, counted over the whole table rather
+    /// than estimated like `hashfull`.
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.count_current(&self.buckets)
     }
-    pub fn clear(&mut self) {
-        self.inner.clear();
+    fn count_current(&self, buckets: &[Bucket]) -> usize {
+        let generation = self.generation();
+        buckets
+            .iter()
+            .flat_map(|bucket| &bucket.0)
+            .map(|slot| Entry::unpack(slot.data.load(Ordering::Relaxed)))
+            .filter(|entry| !entry.is_empty() && entry.age() == generation)
+            .count()
     }
+    /// Looks up a usable score for a position `ply` plies from the root.
     #[must_use]
     #[inline]
-    pub fn get(&mut self, board: &Board, alpha: i32, beta: i32, depth: u8) -> Option<i32> {
-        self.get_entry(board, alpha, beta, depth).map(|entry| entry.eval)
+    pub fn get(&self, board: &Board, alpha: i32, beta: i32, depth: u8, ply: u16) -> Option<i32> {
+        self.get_entry(board, alpha, beta, depth, ply).map(|entry| entry.eval)
     }
     #[must_use]
     #[inline]
-    pub fn get_entry(&mut self, board: &Board, alpha: i32, beta: i32, depth: u8) -> Option<&Entry> {
-        let entry = self.inner.get(&board.zobrist)?;
+    pub fn get_entry(&self, board: &Board, alpha: i32, beta: i32, depth: u8, ply: u16) -> Option<Entry> {
+        let mut entry = self.probe(board.zobrist)?;
         if entry.depth < depth {
             return None;
         }
-        if (entry.nodetype == Nodetype::Exact)
-            || (entry.nodetype == Nodetype::Alpha && entry.eval <= alpha)
-            || (entry.nodetype == Nodetype::Beta && entry.eval >= beta)
+        entry.eval = Eval(entry.eval).relative_to_root(ply).0;
+        let nodetype = entry.nodetype();
+        if (nodetype == Nodetype::Exact)
+            || (nodetype == Nodetype::Alpha && entry.eval <= alpha)
+            || (nodetype == Nodetype::Beta && entry.eval >= beta)
         {
-            self.num_hits += 1;
             return Some(entry);
         }
         None
     }
+    /// The best move stored for this position, whatever depth it was searched to.
+    #[must_use]
+    #[inline]
+    pub fn get_move(&self, board: &Board) -> Option<Move> {
+        self.probe(board.zobrist).map(|entry| entry.mov).filter(|&mov| mov != Move::NULL)
+    }
     #[inline]
+    fn probe(&self, key: Zobrist) -> Option<Entry> {
+        self.bucket(key).0.iter().find_map(|slot| slot.load(key))
+    }
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn insert(
-        &mut self,
+        &self,
         board: &Board,
         seen_positions: &[Zobrist],
         depth: u8,
+        ply: u16,
         eval: i32,
         nodetype: Nodetype,
-        treesize: u64,
+        mov: Option<Move>,
     ) {
-        if eval.abs() == Eval::MATE.0 {
-            return;
-        }
         if seen_positions.iter().filter(|&&sq| sq == board.zobrist).count() > 1 {
             return;
         }
-        let entry = Entry { eval, nodetype, depth, treesize };
-        self.inner.insert(board.zobrist, entry);
+        let key = board.zobrist;
+        let generation = self.generation();
+        let bucket = self.bucket(key);
+        let same_key = bucket.0.iter().enumerate().find_map(|(index, slot)| Some((index, slot.load(key)?)));
+        // a fail low doesn't know a best move, so keep the one found by an earlier search
+        let mov = mov.or(same_key.map(|(_, entry)| entry.mov)).unwrap_or(Move::NULL);
+        let slot = match same_key {
+            // keep deeper results for the same position unless they're from an older search
+            Some((_, entry)) if entry.depth > depth && entry.age() == generation => return,
+            Some((index, _)) => index,
+            None => (0..BUCKET_SIZE)
+                .min_by_key(|&index| {
+                    Entry::unpack(bucket.0[index].data.load(Ordering::Relaxed)).replace_priority(generation)
+                })
+                .expect("Buckets are never empty"),
+        };
+        let eval = Eval(eval).relative_to_node(ply).0;
+        let flags = nodetype as u8 | generation << 2;
+        bucket.0[slot].store(key, Entry { eval, mov, depth, flags });
+    }
+    #[inline]
+    fn bucket(&self, key: Zobrist) -> &Bucket {
+        &self.buckets[key.int() as usize & (self.buckets.len() - 1)]
     }
 }
 
-pub struct Entry {
-    pub eval: i32,
-    pub nodetype: Nodetype,
-    pub depth: u8,
-    pub treesize: u64,
+impl Slot {
+    /// The entry in this slot if it holds the position and wasn't torn by a concurrent write.
+    #[inline]
+    fn load(&self, key: Zobrist) -> Option<Entry> {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        let entry = Entry::unpack(data);
+        (check ^ data == key.int() && !entry.is_empty()).then_some(entry)
+    }
+    #[inline]
+    fn store(&self, key: Zobrist, entry: Entry) {
+        let data = entry.pack();
+        self.check.store(key.int() ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
 }
 
-#[derive(Default)]
-struct NoHasher(u64);
-
-impl Hasher for NoHasher {
+impl Entry {
+    #[must_use]
+    #[inline]
+    pub fn nodetype(self) -> Nodetype {
+        match self.flags & 0b11 {
+            1 => Nodetype::Exact,
+            2 => Nodetype::Alpha,
+            _ => Nodetype::Beta,
+        }
+    }
+    #[inline]
+    fn pack(self) -> u64 {
+        u64::from(self.eval as u32)
+            | u64::from(self.mov.int()) << 32
+            | u64::from(self.depth) << 48
+            | u64::from(self.flags) << 56
+    }
+    #[inline]
+    fn unpack(data: u64) -> Self {
+        Self {
+            eval: data as u32 as i32,
+            mov: Move::from_int((data >> 32) as u16),
+            depth: (data >> 48) as u8,
+            flags: (data >> 56) as u8,
+        }
+    }
     #[inline]
-    fn finish(&self) -> u64 {
-        self.0
+    fn is_empty(self) -> bool {
+        self.flags == 0
     }
-    fn write(&mut self, _bytes: &[u8]) {
-        unreachable!();
+    #[inline]
+    fn age(self) -> u8 {
+        self.flags >> 2
     }
+    /// Lower is replaced first: empty slots, then stale entries, then shallow ones.
     #[inline]
-    fn write_u64(&mut self, i: u64) {
-        self.0 = i;
+    fn replace_priority(self, generation: u8) -> i32 {
+        if self.is_empty() {
+            return i32::MIN;
+        }
+        let age = generation.wrapping_sub(self.age()) & AGE_MASK;
+        i32::from(self.depth) - 8 * i32::from(age)
     }
 }
+
+#[test]
+fn test_transposition_table() {
+    let table = TranspositionTable::new(1);
+    assert!(table.capacity().is_power_of_two());
+    assert!(table.capacity() * size_of::<Slot>() <= 1024 * 1024);
+    assert_eq!(table.hashfull(), 0);
+
+    let board = Board::start_pos();
+    table.insert(&board, &[], 4, 0, 20, Nodetype::Exact, None);
+    assert_eq!(table.get(&board, 0, 0, 4, 0), Some(20));
+    assert_eq!(table.get(&board, 0, 0, 5, 0), None);
+
+    // a shallower result doesn't replace a deeper one from the same search
+    table.insert(&board, &[], 2, 0, 30, Nodetype::Exact, None);
+    assert_eq!(table.get(&board, 0, 0, 1, 0), Some(20));
+
+    table.new_search();
+    table.insert(&board, &[], 2, 0, 30, Nodetype::Exact, None);
+    assert_eq!(table.get(&board, 0, 0, 1, 0), Some(30));
+
+    let mov = Move::new(Square::E2, Square::E4, MoveFlags::DoublePawnPush);
+    table.insert(&board, &[], 3, 0, 30, Nodetype::Beta, Some(mov));
+    assert_eq!(table.get_move(&board), Some(mov));
+    table.insert(&board, &[], 3, 0, 10, Nodetype::Alpha, None);
+    assert_eq!(table.get_move(&board), Some(mov));
+
+    // mate scores are stored relative to the node
+    let mate = -Eval::mated_in(9);
+    table.insert(&board, &[], 5, 4, mate.0, Nodetype::Exact, None);
+    assert_eq!(table.get(&board, 0, 0, 1, 6), Some((-Eval::mated_in(11)).0));
+
+    // threads writing the same slot at once never leave a mix of their entries behind
+    std::thread::scope(|scope| {
+        for eval in [1234, -4321] {
+            let table = &table;
+            let board = &board;
+            scope.spawn(move || {
+                for _ in 0..10_000 {
+                    table.insert(board, &[], 9, 0, eval, Nodetype::Exact, None);
+                    let found = table.get(board, 0, 0, 9, 0);
+                    assert!(matches!(found, None | Some(1234 | -4321)), "{found:?}");
+                }
+            });
+        }
+    });
+
+    table.clear();
+    assert_eq!(table.get(&board, 0, 0, 1, 0), None);
+    assert_eq!(table.get_move(&board), None);
+}
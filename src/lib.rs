@@ -11,7 +11,7 @@ pub mod prelude {
         core::{
             bitboard::Bitboard,
             board::Board,
-            can_castle::CanCastle,
+            can_castle::{CanCastle, CastlingRooks},
             fen,
             move_flags::{Castle, MoveFlags, Promotion},
             movegen::{self, MoveGenerator},
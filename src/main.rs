@@ -1,13 +1,22 @@
 use std::{
     fmt::Write,
     io::BufRead as _,
+    sync::{atomic::Ordering, Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
 use petty_chess::{
-    engine::transposition::TranspositionTable,
+    core::{
+        epd::Epd,
+        perft::{parse_perft_line, PerftStats, PerftTable},
+    },
+    engine::{
+        bench::{Verdict, BENCH_DEPTH, BENCH_POSITIONS},
+        EngineOption, Options, SearchLimits, SearchSignals, MAX_DEPTH,
+    },
     prelude::*,
-    uci::{GoCommand, TimeControl, UciMessage, UciResponse},
+    uci::{BenchCommand, GoCommand, Info, TimeControl, UciMessage, UciResponse},
 };
 #[cfg(feature = "tracing")]
 use tracing::{debug, Level};
@@ -42,20 +51,29 @@ fn main() {
         } else {
             #[cfg(feature = "tracing")]
             tracing::warn!("Unknown command: '{line}'");
-            eprintln!("Unknown command: '{line}'. Type help for more information.",);
+            eprintln!("Unknown command: '{line}'. Type help for more information.");
         }
     }
 }
 
 pub struct Application {
-    engine: Engine,
+    engine: Arc<Mutex<Engine>>,
+    signals: Arc<SearchSignals>,
+    search_thread: Option<JoinHandle<()>>,
     running: bool,
     debug: bool,
 }
 
 impl Default for Application {
     fn default() -> Self {
-        Self { engine: Engine::new(Board::start_pos()), running: true, debug: false }
+        let engine = Engine::new(Board::start_pos());
+        Self {
+            signals: Arc::clone(&engine.signals),
+            engine: Arc::new(Mutex::new(engine)),
+            search_thread: None,
+            running: true,
+            debug: false,
+        }
     }
 }
 
@@ -67,10 +85,15 @@ impl Application {
         match msg {
             Uci::Uci => self.respond_with_id(),
             Uci::Isready => self.respond(UciResponse::Readyok),
-            Uci::Setoption { .. } => {}
+            Uci::Setoption { id, value } => {
+                let result = self.engine().set_option(&id, value.as_deref());
+                if let Err(err) = result {
+                    self.respond(UciResponse::Info(Box::new(Info { string: Some(err), ..Info::default() })));
+                }
+            }
             Uci::Debug(on) => self.debug = on,
             Uci::Register(_reg) => {}
-            Uci::Ucinewgame => *self = Self::default(),
+            Uci::Ucinewgame => self.engine().new_game(),
             Uci::Position { fen, moves } => {
                 if let Some(board) = Board::from_fen(&fen) {
                     self.startpos_moves(board, moves);
@@ -80,80 +103,266 @@ impl Application {
                 }
             }
             Uci::Go(command) => self.go(command),
-            Uci::Stop => self.engine.force_cancelled = true,
-            Uci::PonderHit => {}
-            Uci::Quit => self.running = false,
-            Uci::Perft { depth } => self.go_perft(depth.unwrap_or(1) as u8),
+            Uci::Stop => self.stop(),
+            Uci::PonderHit => self.signals.pondering.store(false, Ordering::Relaxed),
+            Uci::Quit => {
+                self.stop();
+                self.running = false;
+            }
+            Uci::Perft { depth, threads } => self.go_perft(depth.unwrap_or(1) as u8, threads),
+            Uci::PerftDetailed { depth } => self.go_perft_detailed(depth.unwrap_or(1) as u8),
+            Uci::PerftVerify { file } => self.verify_perft(&file),
+            Uci::Bench(command) => self.bench(command),
             Uci::Display => self.display(),
         }
     }
     fn respond_with_id(&self) {
         self.respond(UciResponse::Id { name: "Petty Chess".into(), author: "Dorje Gilfillan".into() });
+        for option in EngineOption::ALL {
+            self.respond(UciResponse::Option { name: option.name().into(), option: option.option_type() });
+        }
         self.respond(UciResponse::Uciok);
     }
     fn respond(&self, response: UciResponse) {
         println!("{response}");
     }
+    /// Stops any running search and waits for it to send its `bestmove`.
+    fn stop(&mut self) {
+        self.signals.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.search_thread.take() {
+            handle.join().unwrap();
+        }
+    }
+    /// Gives access to the engine, stopping the search if one is running.
+    fn engine(&mut self) -> MutexGuard<'_, Engine> {
+        self.stop();
+        self.engine.lock().unwrap()
+    }
     fn startpos_moves(&mut self, position: Board, moves: Moves) {
-        self.engine.seen_positions = vec![position.zobrist];
-        self.engine.board = position.clone();
+        let mut engine = self.engine();
+        engine.seen_positions = vec![position.zobrist];
+        engine.board = position.clone();
+        engine.board.chess960 |= engine.options.chess960;
         for mov in moves {
-            let legal_moves = self.engine.board.gen_legal_moves();
-            let Some(&mov) = legal_moves.iter().find(|m| {
-                (m.from(), m.to(), m.flags().promotion()) == (mov.from(), mov.to(), mov.flags().promotion())
-            }) else {
+            let Some(mov) = engine.board.find_legal_move(mov) else {
                 eprintln!("Invalid move: {mov}");
                 break;
             };
             if mov.flags().is_capture() {
-                self.engine.seen_positions.clear();
+                engine.seen_positions.clear();
             }
-            self.engine.seen_positions.push(self.engine.board.zobrist);
-            self.engine.board.make_move(mov);
+            let zobrist = engine.board.zobrist;
+            engine.seen_positions.push(zobrist);
+            engine.board.make_move(mov);
         }
-        self.engine.seen_positions.push(self.engine.board.zobrist);
+        let zobrist = engine.board.zobrist;
+        engine.seen_positions.push(zobrist);
     }
     fn go(&mut self, command: GoCommand) {
-        #[cfg(feature = "tracing")]
+        let mut engine = self.engine();
+        set_time_available(&mut engine, command.time_control);
+        engine.limits = SearchLimits::from(&command);
+        engine.searchmoves = command
+            .searchmoves
+            .map(|moves| moves.into_iter().filter_map(|mov| engine.board.find_legal_move(mov)).collect());
+        drop(engine);
+
+        self.signals.stop.store(false, Ordering::Relaxed);
+        self.signals.pondering.store(command.ponder, Ordering::Relaxed);
+        let engine = Arc::clone(&self.engine);
+        let signals = Arc::clone(&self.signals);
+        self.search_thread = Some(thread::spawn(move || {
+            #[cfg(feature = "tracing")]
+            let start = Instant::now();
+            let mut engine = engine.lock().unwrap();
+            let best_move = engine.search();
+            // bestmove may only be sent once the opponent's move is known
+            while signals.pondering.load(Ordering::Relaxed) && !signals.stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let mov = engine.board.uci_move(best_move);
+            let ponder = engine.ponder_move().map(|mov| engine.board.uci_move(mov));
+            println!("{}", UciResponse::Bestmove { mov, ponder });
+            #[cfg(feature = "tracing")]
+            tracing::info!("Time taken: {:?}", start.elapsed());
+            #[cfg(feature = "tracing")]
+            tracing::info!("Num transpositions: {}", engine.tt_hits);
+        }));
+    }
+    /// Perft divide, using the `Threads` option unless the command gives a thread count.
+    fn go_perft(&mut self, depth: u8, threads: Option<u32>) {
         let start = Instant::now();
-        self.set_time_available(command.time_control);
-        let best_move = self.engine.search();
-        self.respond(UciResponse::Bestmove { mov: best_move, ponder: None });
-        #[cfg(feature = "tracing")]
-        tracing::info!("Time taken: {:?}", start.elapsed());
-        #[cfg(feature = "tracing")]
-        tracing::info!("Num transpositions: {}", self.engine.transposition_table.num_hits);
+        let engine = self.engine();
+        let threads = threads.map_or(engine.options.threads, |threads| threads as usize);
+        let table = PerftTable::new(engine.options.hash_mb);
+        let mut divide = engine.board.perft_divide(depth, threads, &table);
+        divide.sort_by_key(|(mov, _)| mov.from().int() + mov.to().int());
+        for (mov, count) in &divide {
+            eprintln!("{mov}: {count}");
+        }
+        eprintln!("\nTime taken: {:?}", start.elapsed());
+        eprintln!("Nodes searched: {}", divide.iter().map(|(_, count)| count).sum::<u64>());
     }
-    fn go_perft(&mut self, depth: u8) {
+    /// Divides the leaf count between the root moves, then counts the moves at every depth by kind.
+    fn go_perft_detailed(&mut self, depth: u8) {
         let start = Instant::now();
-        let total = perft(&mut self.engine.board, depth);
+        let board = &mut self.engine().board;
+        let mut stats = vec![PerftStats::default(); depth.into()];
+        let mut moves = board.gen_legal_moves();
+        moves.sort_by_key(|mov| mov.from().int() + mov.to().int());
+        for mov in moves {
+            let mut root = PerftStats::default();
+            let unmake = board.make_move(mov);
+            root.record(board, mov);
+            let below = board.perft_stats(depth.saturating_sub(1));
+            board.unmake_move(unmake);
+
+            eprintln!("{mov}: {}", below.last().map_or(1, |leaves| leaves.nodes));
+            if let Some(first) = stats.first_mut() {
+                *first += root;
+            }
+            for (total, below) in stats.iter_mut().skip(1).zip(below) {
+                *total += below;
+            }
+        }
         eprintln!("\nTime taken: {:?}", start.elapsed());
-        eprintln!("Nodes searched: {total}");
+        eprintln!(
+            "\n{:>5} {:>12} {:>10} {:>8} {:>8} {:>10} {:>9} {:>10} {:>7} {:>10}",
+            "Depth",
+            "Nodes",
+            "Captures",
+            "E.p.",
+            "Castles",
+            "Promotions",
+            "Checks",
+            "Discovered",
+            "Double",
+            "Checkmates"
+        );
+        for (depth, stats) in stats.iter().enumerate() {
+            eprintln!(
+                "{:>5} {:>12} {:>10} {:>8} {:>8} {:>10} {:>9} {:>10} {:>7} {:>10}",
+                depth + 1,
+                stats.nodes,
+                stats.captures,
+                stats.en_passants,
+                stats.castles,
+                stats.promotions,
+                stats.checks,
+                stats.discovered_checks,
+                stats.double_checks,
+                stats.checkmates
+            );
+        }
     }
-    fn set_time_available(&mut self, time_control: TimeControl) {
-        match time_control {
-            // TODO - ponder
-            TimeControl::Ponder => self.engine.time_available = Duration::MAX,
-            TimeControl::TimeLeft { wtime, btime, wincr, bincr, .. } => {
-                let (total, incr) =
-                    if self.engine.board.active_side == White { (wtime, wincr) } else { (btime, bincr) };
-                let estimated_total_moves = i32::from(30.max(self.engine.board.fullmove_counter + 10));
-                let moves_to_end = estimated_total_moves - i32::from(self.engine.board.fullmove_counter);
-                let time_per_move = total.div_f32(moves_to_end as f32);
-                self.engine.time_available = (time_per_move + incr).min(total);
+    /// Runs perft on every line of a file of expected results and reports the ones that differ.
+    fn verify_perft(&mut self, file: &str) {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("Could not read {file}: {err}");
+                return;
+            }
+        };
+        let start = Instant::now();
+        let (mut passed, mut failed) = (0, 0);
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let Some((mut board, results)) = parse_perft_line(line) else {
+                eprintln!("Invalid line: {line}");
+                failed += 1;
+                continue;
+            };
+            for (depth, expected) in results {
+                let count = board.run_perft(depth);
+                if count == expected {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                    eprintln!("FAILED {} depth {depth}: expected {expected}, got {count}", board.to_fen());
+                }
             }
-            TimeControl::MoveTime(time) => self.engine.time_available = time,
-            TimeControl::Infinite => self.engine.time_available = Duration::MAX,
         }
+        eprintln!("\nPassed: {passed}, failed: {failed}");
+        eprintln!("Time taken: {:?}", start.elapsed());
+    }
+    /// Searches every position to a fixed depth, or for a fixed time, and reports which were solved.
+    /// The total node count at a fixed depth only changes when the search does.
+    fn bench(&mut self, command: BenchCommand) {
+        let positions: Vec<Epd> = match &command.file {
+            Some(file) => match std::fs::read_to_string(file) {
+                Ok(text) => text
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .filter_map(|line| {
+                        let epd = Epd::parse(line);
+                        if epd.is_none() {
+                            eprintln!("Invalid EPD: {line}");
+                        }
+                        epd
+                    })
+                    .collect(),
+                Err(err) => {
+                    eprintln!("Could not read {file}: {err}");
+                    return;
+                }
+            },
+            None => BENCH_POSITIONS
+                .iter()
+                .map(|line| Epd::parse(line).expect("Bench positions should be valid EPD"))
+                .collect(),
+        };
+        let depth = match (command.depth, command.movetime) {
+            (Some(depth), _) => Some(depth.min(MAX_DEPTH.into()) as u8),
+            (None, Some(_)) => None,
+            (None, None) => Some(BENCH_DEPTH),
+        };
+        let limits = SearchLimits { depth, ..SearchLimits::default() };
+
+        // a separate engine leaves the game in progress alone, and a single thread keeps the node
+        // count reproducible
+        let options = Options { threads: 1, ..self.engine().options.clone() };
+        let mut engine = Engine::with_options(Board::start_pos(), options);
+        engine.quiet = true;
+        let start = Instant::now();
+        let results = engine.bench(&positions, limits, command.movetime.unwrap_or(Duration::MAX));
+        let elapsed = start.elapsed();
+
+        for (index, (epd, result)) in positions.iter().zip(&results).enumerate() {
+            let id = epd.id().map_or_else(|| (index + 1).to_string(), str::to_string);
+            let status = match result.verdict {
+                Some(Verdict::Solved) => "solved",
+                Some(Verdict::Unsolved) => "unsolved",
+                Some(Verdict::Invalid) => "invalid",
+                None => "",
+            };
+            let mov = epd.board.uci_move(result.best_move).to_string();
+            eprintln!(
+                "{id:<16} {mov:<6} {status:<8} {:>10} nodes {:>7} ms",
+                result.nodes,
+                result.time.as_millis()
+            );
+        }
+        let count = |verdict| results.iter().filter(|result| result.verdict == Some(verdict)).count();
+        let (solved, unsolved, invalid) =
+            (count(Verdict::Solved), count(Verdict::Unsolved), count(Verdict::Invalid));
+        let nodes: u64 = results.iter().map(|result| result.nodes).sum();
+        eprintln!("\nSolved: {solved}/{}", solved + unsolved);
+        if invalid > 0 {
+            eprintln!("Invalid: {invalid}");
+        }
+        eprintln!("Total time (ms): {}", elapsed.as_millis());
+        eprintln!("Nodes searched: {nodes}");
+        eprintln!("Nodes/second: {}", (nodes as f64 / elapsed.as_secs_f64()) as u64);
     }
     fn display(&mut self) {
+        let mut engine = self.engine();
         let mut out = String::new();
         for rank in (0..8).rev() {
             out.push_str("+---+---+---+---+---+---+---+---+\n|");
             for file in 0..8 {
                 out.push(' ');
                 let square = Square::new(Rank(rank), File(file));
-                let piece = self.engine.board.get_square(square);
+                let piece = engine.board.get_square(square);
                 out.push(piece.map_or(' ', Piece::symbol));
                 out.push_str(" |");
             }
@@ -162,30 +371,28 @@ impl Application {
         out.push_str("+---+---+---+---+---+---+---+---+\n");
         out.push_str("  a   b   c   d   e   f   g   h  \n");
         println!("{out}");
-        println!("Fen: {}", self.engine.board.to_fen());
-        println!("Key: {:?}", self.engine.board.zobrist);
+        println!("Fen: {}", engine.board.to_fen());
+        println!("Key: {:?}", engine.board.zobrist);
         print!("Checkers: ");
-        self.engine.board.checkers.for_each(|sq| {
+        engine.board.checkers.for_each(|sq| {
             print!(" {sq}");
         });
         println!();
-        println!("Direct Eval: {:?}", self.engine.raw_evaluation());
+        println!("Direct Eval: {:?}", engine.raw_evaluation());
     }
 }
 
-fn perft(board: &mut Board, depth: u8) -> u64 {
-    let mut total = 0;
-    let mut moves = board.gen_legal_moves();
-
-    let mut table = TranspositionTable::default();
-    moves.sort_by_key(|mov| mov.from().int() + mov.to().int());
-
-    for mov in moves {
-        let unmake = board.make_move(mov);
-        let count = board.run_perft_with_table(&mut table, depth - 1);
-        total += count;
-        board.unmake_move(unmake);
-        eprintln!("{mov}: {count}");
+fn set_time_available(engine: &mut Engine, time_control: TimeControl) {
+    let overhead = engine.options.move_overhead;
+    match time_control {
+        TimeControl::TimeLeft { wtime, btime, wincr, bincr, .. } => {
+            let (total, incr) = if engine.board.active_side == White { (wtime, wincr) } else { (btime, bincr) };
+            let estimated_total_moves = i32::from(30.max(engine.board.fullmove_counter + 10));
+            let moves_to_end = estimated_total_moves - i32::from(engine.board.fullmove_counter);
+            let time_per_move = total.div_f32(moves_to_end as f32);
+            engine.time_available = (time_per_move + incr).min(total).saturating_sub(overhead);
+        }
+        TimeControl::MoveTime(time) => engine.time_available = time.saturating_sub(overhead),
+        TimeControl::Infinite => engine.time_available = Duration::MAX,
     }
-    total
 }
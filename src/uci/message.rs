@@ -15,7 +15,12 @@ pub enum UciMessage {
     Stop,
     PonderHit,
     Quit,
-    Perft { depth: Option<u32> },
+    Perft { depth: Option<u32>, threads: Option<u32> },
+    /// Perft with the moves at every depth counted by kind.
+    PerftDetailed { depth: Option<u32> },
+    /// Compares perft results against a file of expected node counts.
+    PerftVerify { file: String },
+    Bench(BenchCommand),
     Display,
 }
 
@@ -32,11 +37,19 @@ pub struct GoCommand {
     pub depth: Option<u32>,
     pub nodes: Option<u64>,
     pub mate: Option<u32>,
+    pub ponder: bool,
+}
+
+/// Searches the positions of an EPD file, or the built-in bench positions without one.
+#[derive(Default, Debug, Clone, PartialEq, PartialOrd)]
+pub struct BenchCommand {
+    pub file: Option<String>,
+    pub depth: Option<u32>,
+    pub movetime: Option<Duration>,
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum TimeControl {
-    Ponder,
     TimeLeft {
         wtime: Duration,
         btime: Duration,
@@ -2,5 +2,5 @@ mod message;
 mod parser;
 mod response;
 
-pub use message::{GoCommand, Registration, TimeControl, UciMessage};
+pub use message::{BenchCommand, GoCommand, Registration, TimeControl, UciMessage};
 pub use response::{Bound, Info, OptionType, Score, UciResponse};
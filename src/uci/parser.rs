@@ -1,6 +1,6 @@
 use std::{str::FromStr, time::Duration};
 
-use super::{GoCommand, Registration, TimeControl, UciMessage as Uci};
+use super::{BenchCommand, GoCommand, Registration, TimeControl, UciMessage as Uci};
 use crate::prelude::*;
 
 impl FromStr for Uci {
@@ -11,6 +11,7 @@ impl FromStr for Uci {
 }
 
 impl Uci {
+    #[must_use]
     pub fn parse(input: &str) -> Option<Self> {
         let mut tokens = Lexer::new(input);
 
@@ -25,10 +26,16 @@ impl Uci {
                 "isready" => Some(Uci::Isready),
                 "setoption" => {
                     let "name" = tokens.bump()?.as_str() else { continue };
-                    let id = tokens.bump()?;
+                    // names and values may both contain spaces
+                    let mut id = tokens.bump()?;
                     let mut value = None;
-                    if tokens.bump() == Some("value".to_string()) {
-                        value = Some(tokens.bump()?);
+                    while let Some(token) = tokens.bump() {
+                        if token == "value" {
+                            value = Some(tokens.rest().to_string()).filter(|value| !value.is_empty());
+                            break;
+                        }
+                        id.push(' ');
+                        id.push_str(&token);
                     }
                     Some(Uci::Setoption { id, value })
                 }
@@ -41,9 +48,9 @@ impl Uci {
                         };
                         match token.as_str() {
                             "later" => break Some(Uci::Register(Registration::Later)),
-                            "name" => name = tokens.bump().map(String::from),
-                            "code" => code = tokens.bump().map(String::from),
-                            _ => continue,
+                            "name" => name = tokens.bump(),
+                            "code" => code = tokens.bump(),
+                            _ => {}
                         }
                     }
                 }
@@ -55,19 +62,53 @@ impl Uci {
                         "fen" => tokens.fen()?,
                         _ => continue,
                     });
-                    Some(Uci::Position { fen, moves: tokens.moves() })
+                    Some(Uci::Position { fen, moves: tokens.position_moves() })
                 }
-                "fen" => Some(Uci::Position { fen: tokens.fen()?.into(), moves: tokens.moves() }),
+                "fen" => Some(Uci::Position { fen: tokens.fen()?.into(), moves: tokens.position_moves() }),
                 "go" => Some(Uci::Go(Self::parse_go(&mut tokens))),
                 "stop" => Some(Uci::Stop),
                 "ponderhit" => Some(Uci::PonderHit),
                 "quit" => Some(Uci::Quit),
-                "perft" => Some(Uci::Perft { depth: tokens.bump_spin().map(|i| i as u32) }),
+                "perft" => Some(match tokens.peek().as_deref() {
+                    Some("detailed") => {
+                        tokens.bump();
+                        Uci::PerftDetailed { depth: tokens.bump_spin().map(|i| i as u32) }
+                    }
+                    Some("verify") => {
+                        tokens.bump();
+                        Uci::PerftVerify { file: tokens.bump_raw()?.to_string() }
+                    }
+                    _ => {
+                        let depth = tokens.bump_spin().map(|i| i as u32);
+                        let threads = match tokens.bump().as_deref() {
+                            Some("threads") => tokens.bump_spin().map(|i| i as u32),
+                            _ => None,
+                        };
+                        Uci::Perft { depth, threads }
+                    }
+                }),
+                "bench" => Some(Uci::Bench(Self::parse_bench(&mut tokens, None))),
+                "epdtest" => {
+                    let file = tokens.bump_raw()?.to_string();
+                    Some(Uci::Bench(Self::parse_bench(&mut tokens, Some(file))))
+                }
                 "d" => Some(Uci::Display),
                 _ => continue,
             };
         }
     }
+    fn parse_bench(tokens: &mut Lexer, file: Option<String>) -> BenchCommand {
+        let mut depth = None;
+        let mut movetime = None;
+        while let Some(token) = tokens.bump() {
+            match token.as_str() {
+                "depth" => depth = tokens.bump_spin().map(|i| i as u32).or(depth),
+                "movetime" => movetime = tokens.bump_spin().map(Duration::from_millis).or(movetime),
+                _ => {}
+            }
+        }
+        BenchCommand { file, depth, movetime }
+    }
     fn parse_go(tokens: &mut Lexer) -> GoCommand {
         let mut command = GoCommand {
             searchmoves: None,
@@ -75,17 +116,17 @@ impl Uci {
             depth: None,
             nodes: None,
             mate: None,
+            ponder: false,
         };
         let mut wtime = Duration::ZERO;
         let mut btime = Duration::ZERO;
         let mut wincr = Duration::ZERO;
         let mut bincr = Duration::ZERO;
         let mut moves_to_go = None;
-        loop {
-            let Some(token) = tokens.bump() else { break };
+        while let Some(token) = tokens.bump() {
             match token.as_str() {
                 "infinite" => command.time_control = TimeControl::Infinite,
-                "ponder" => command.time_control = TimeControl::Ponder,
+                "ponder" => command.ponder = true,
                 "movetime" => {
                     let Some(movetime) = tokens.bump_spin() else { continue };
                     command.time_control = TimeControl::MoveTime(Duration::from_millis(movetime));
@@ -107,7 +148,7 @@ impl Uci {
                 "mate" => command.mate = tokens.bump_spin().map(|i| i as u32).or(command.mate),
                 "nodes" => command.nodes = tokens.bump_spin().or(command.nodes),
                 "depth" => command.depth = tokens.bump_spin().map(|i| i as u32).or(command.depth),
-                _ => continue,
+                _ => {}
             }
         }
         command
@@ -124,13 +165,17 @@ impl<'a> Lexer<'a> {
         Self { remaining: input.trim() }
     }
     fn bump(&mut self) -> Option<String> {
+        self.bump_raw().map(str::to_ascii_lowercase)
+    }
+    /// The next token without changing its case, e.g. a file path.
+    fn bump_raw(&mut self) -> Option<&'a str> {
         if self.remaining.is_empty() {
             return None;
         }
         let ws = self.remaining.find(|c: char| c.is_whitespace()).unwrap_or(self.remaining.len());
         let token = &self.remaining[..ws];
         self.remaining = self.remaining[ws..].trim();
-        Some(token.to_ascii_lowercase())
+        Some(token)
     }
     fn bump_spin(&mut self) -> Option<u64> {
         if self.remaining.is_empty() {
@@ -141,15 +186,24 @@ impl<'a> Lexer<'a> {
         self.remaining = self.remaining[end..].trim();
         token.parse().ok()
     }
+    /// Moves up to the first token that isn't one, which is left for the caller.
     fn moves(&mut self) -> Moves {
         let mut moves = Moves::new();
-        loop {
-            let Some(token) = self.bump() else { break };
-            let Ok(mov) = token.parse() else { continue };
+        while let Some(Ok(mov)) = self.peek().map(|token| token.parse()) {
+            self.bump();
             moves.push(mov);
         }
         moves
     }
+    fn position_moves(&mut self) -> Moves {
+        if self.peek().as_deref() == Some("moves") {
+            self.bump();
+        }
+        self.moves()
+    }
+    fn rest(&mut self) -> &'a str {
+        std::mem::take(&mut self.remaining)
+    }
     fn peek(&self) -> Option<String> {
         self.clone().bump()
     }
@@ -172,6 +226,8 @@ impl<'a> Lexer<'a> {
 }
 
 #[test]
+// uci gives times in milliseconds
+#[allow(clippy::duration_suboptimal_units)]
 fn test_uci_parsing() {
     assert_eq!(
         "asdhasud    poSition   StarTpos 123124  213y1279asdzxc".parse(),
@@ -182,12 +238,41 @@ fn test_uci_parsing() {
         Ok(Uci::Position { fen: fen::PERFT_POSITION_3.into(), moves: Moves::new() })
     );
 
+    assert_eq!(
+        "setoption name Clear Hash".parse(),
+        Ok(Uci::Setoption { id: "clear hash".into(), value: None })
+    );
+    assert_eq!(
+        "setoption name Move Overhead value 30".parse(),
+        Ok(Uci::Setoption { id: "move overhead".into(), value: Some("30".into()) })
+    );
+
     assert_eq!("hgfgfas debug on garbage".parse(), Ok(Uci::Debug(true)));
     assert_eq!("debug off garbage".parse(), Ok(Uci::Debug(false)));
 
-    assert_eq!("perft    garbage".parse(), Ok(Uci::Perft { depth: None }));
-    assert_eq!("perft    4 ".parse(), Ok(Uci::Perft { depth: Some(4) }));
-    assert_eq!("perft    8a".parse(), Ok(Uci::Perft { depth: Some(8) }));
+    assert_eq!("perft    garbage".parse(), Ok(Uci::Perft { depth: None, threads: None }));
+    assert_eq!("perft    4 ".parse(), Ok(Uci::Perft { depth: Some(4), threads: None }));
+    assert_eq!("perft    8a".parse(), Ok(Uci::Perft { depth: Some(8), threads: None }));
+    assert_eq!("perft 7 threads 4".parse(), Ok(Uci::Perft { depth: Some(7), threads: Some(4) }));
+    assert_eq!("perft detailed 3".parse(), Ok(Uci::PerftDetailed { depth: Some(3) }));
+    assert_eq!(
+        "perft verify Suites/perft.epd".parse(),
+        Ok(Uci::PerftVerify { file: "Suites/perft.epd".into() })
+    );
+
+    assert_eq!("bench".parse(), Ok(Uci::Bench(BenchCommand::default())));
+    assert_eq!(
+        "epdtest Suites/WAC.epd movetime 500".parse(),
+        Ok(Uci::Bench(BenchCommand {
+            file: Some("Suites/WAC.epd".into()),
+            depth: None,
+            movetime: Some(Duration::from_millis(500))
+        }))
+    );
+    assert_eq!(
+        "bench depth 9".parse(),
+        Ok(Uci::Bench(BenchCommand { depth: Some(9), ..BenchCommand::default() }))
+    );
 
     assert_eq!("1283698 go snkdmzx9".parse(), Ok(Uci::Go(GoCommand::default())));
     assert_eq!(
@@ -197,7 +282,22 @@ fn test_uci_parsing() {
             nodes: Some(5),
             mate: Some(4),
             searchmoves: None,
-            time_control: TimeControl::MoveTime(Duration::from_millis(10))
+            time_control: TimeControl::MoveTime(Duration::from_millis(10)),
+            ponder: false,
+        }))
+    );
+    assert_eq!(
+        "go ponder wtime 1000 btime 2000".parse(),
+        Ok(Uci::Go(GoCommand {
+            time_control: TimeControl::TimeLeft {
+                wtime: Duration::from_millis(1000),
+                btime: Duration::from_millis(2000),
+                wincr: Duration::ZERO,
+                bincr: Duration::ZERO,
+                moves_to_go: None,
+            },
+            ponder: true,
+            ..GoCommand::default()
         }))
     );
     assert_eq!(
@@ -227,3 +327,42 @@ fn test_uci_parsing() {
         }))
     );
 }
+
+#[test]
+// uci gives times in milliseconds
+#[allow(clippy::duration_suboptimal_units)]
+fn test_move_list_parsing() {
+    // a move list ends at the first token that isn't a move
+    assert_eq!(
+        "position startpos moves e2e4 e7e5".parse(),
+        Ok(Uci::Position {
+            fen: fen::STARTING_FEN.into(),
+            moves: vec![
+                Move::new(Square::E2, Square::E4, MoveFlags::Quiet),
+                Move::new(Square::E7, Square::E5, MoveFlags::Quiet)
+            ]
+            .into()
+        })
+    );
+    assert_eq!(
+        "go searchmoves e2e4 d2d4 depth 5 wtime 1000".parse(),
+        Ok(Uci::Go(GoCommand {
+            searchmoves: Some(
+                vec![
+                    Move::new(Square::E2, Square::E4, MoveFlags::Quiet),
+                    Move::new(Square::D2, Square::D4, MoveFlags::Quiet)
+                ]
+                .into()
+            ),
+            depth: Some(5),
+            time_control: TimeControl::TimeLeft {
+                wtime: Duration::from_millis(1000),
+                btime: Duration::ZERO,
+                wincr: Duration::ZERO,
+                bincr: Duration::ZERO,
+                moves_to_go: None,
+            },
+            ..GoCommand::default()
+        }))
+    );
+}
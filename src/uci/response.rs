@@ -31,6 +31,8 @@ pub enum Requirement {
 pub struct Info {
     pub depth: Option<u32>,
     pub seldepth: Option<u32>,
+    /// Which of the best lines this is, when more than one is reported.
+    pub multipv: Option<u32>,
     pub time: Option<Duration>,
     pub nodes: Option<u64>,
     pub pv: Option<Moves>,
@@ -107,6 +109,7 @@ impl fmt::Display for Info {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", Maybe(" depth", &self.depth))?;
         write!(f, "{}", Maybe(" seldepth", &self.seldepth))?;
+        write!(f, "{}", Maybe(" multipv", &self.multipv))?;
         write!(f, "{}", Maybe(" score", &self.score))?;
         write!(f, "{}", Maybe(" nodes", &self.nodes))?;
         write!(f, "{}", Maybe(" nps", &self.nps))?;
@@ -126,7 +129,7 @@ impl fmt::Display for Info {
             write!(f, " {} {}", Maybe("", cpunr), List(" ", line))?;
         }
 
-        write!(f, "{}", Maybe(" str", &self.string))
+        write!(f, "{}", Maybe(" string", &self.string))
     }
 }
 